@@ -1,6 +1,8 @@
 use alloc::alloc::{GlobalAlloc, Layout};
 use core::mem;
 use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use x86_64::instructions::interrupts::without_interrupts;
 use x86_64::{
     structures::paging::{
         mapper::MapToError,
@@ -17,7 +19,16 @@ use x86_64::{
 static ALLOCATOR: Locked<LinkedListAllocator> = Locked::new(LinkedListAllocator::new());
 
 pub const HEAP_START: usize = 0x_4444_4444_0000;
-pub const HEAP_SIZE: usize = 100 * 1024; // 100 KiB
+// 2 MiB for the kernel, thread stacks included, and room for the scrollback of every
+// console filled up on top.
+pub const HEAP_SIZE: usize = 2 * 1024 * 1024 + crate::vga_buffer::SCROLLBACK_SIZE;
+
+// Bytes handed out, including what the allocator rounds sizes up to.
+static USED: AtomicUsize = AtomicUsize::new(0);
+
+pub fn used() -> usize {
+    USED.load(Ordering::Relaxed)
+}
 
 pub fn init_heap(mapper: &mut impl Mapper<Size4KiB>, frame_allocator: &mut impl FrameAllocator<Size4KiB>) -> Result<(), MapToError<Size4KiB>> {
     let page_range = {
@@ -145,29 +156,40 @@ impl LinkedListAllocator {
     }
 }
 
+// Interrupts stay disabled while the allocator is locked, so a thread can never be
+// preempted while holding the lock and code that runs with interrupts disabled can
+// still allocate.
 unsafe impl GlobalAlloc for Locked<LinkedListAllocator> {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
         let (size, align) = LinkedListAllocator::size_align(layout);
-        let mut allocator = self.lock();
 
-        if let Some((region, alloc_start)) = allocator.find_region(size, align) {
-            let alloc_end = alloc_start.checked_add(size).expect("Overflow");
-            let excess_size = region.end_addr() - alloc_end;
+        without_interrupts(|| {
+            let mut allocator = self.lock();
 
-            if 0 < excess_size {
-                allocator.add_free_region(alloc_end, excess_size);
-            }
+            if let Some((region, alloc_start)) = allocator.find_region(size, align) {
+                let alloc_end = alloc_start.checked_add(size).expect("Overflow");
+                let excess_size = region.end_addr() - alloc_end;
 
-            alloc_start as *mut u8
-        } else {
-            ptr::null_mut()
-        }
+                if 0 < excess_size {
+                    allocator.add_free_region(alloc_end, excess_size);
+                }
+
+                USED.fetch_add(size, Ordering::Relaxed);
+
+                alloc_start as *mut u8
+            } else {
+                ptr::null_mut()
+            }
+        })
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
         let (size, _) = LinkedListAllocator::size_align(layout);
 
-        self.lock().add_free_region(ptr as usize, size);
+        without_interrupts(|| {
+            self.lock().add_free_region(ptr as usize, size);
+            USED.fetch_sub(size, Ordering::Relaxed);
+        });
     }
 }
 
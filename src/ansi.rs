@@ -0,0 +1,126 @@
+// Splits output into printable bytes and ANSI escape sequences. It only recognizes them,
+// what they do is up to whoever draws the output.
+
+const ESCAPE: u8 = 0x1B;
+
+// Parameters beyond this are parsed but dropped.
+const MAX_PARAMETERS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Csi {
+    parameters: [u16; MAX_PARAMETERS],
+    count: usize,
+    pub final_byte: u8
+}
+
+impl Csi {
+    // Missing parameters, and ones given as 0, take the default.
+    pub fn parameter(&self, index: usize, default: u16) -> u16 {
+        match self.parameters[..self.count].get(index) {
+            Some(0) | None => default,
+            Some(value) => *value
+        }
+    }
+
+    // Like parameter, but keeps an explicit 0, as erase and SGR need it.
+    pub fn raw_parameter(&self, index: usize) -> u16 {
+        self.parameters[..self.count].get(index).copied().unwrap_or(0)
+    }
+
+    pub fn parameters(&self) -> &[u16] {
+        &self.parameters[..self.count.max(1)]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Print(u8),
+    Csi(Csi),
+    SaveCursor,
+    RestoreCursor
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    Csi
+}
+
+pub struct Parser {
+    state: State,
+    csi: Csi
+}
+
+impl Parser {
+    pub const fn new() -> Parser {
+        Parser {
+            state: State::Ground,
+            csi: Csi {
+                parameters: [0; MAX_PARAMETERS],
+                count: 0,
+                final_byte: 0
+            }
+        }
+    }
+
+    pub fn advance(&mut self, byte: u8) -> Option<Action> {
+        match self.state {
+            State::Ground => {
+                if byte == ESCAPE {
+                    self.state = State::Escape;
+                    return None;
+                }
+
+                Some(Action::Print(byte))
+            },
+            State::Escape => {
+                self.state = State::Ground;
+
+                match byte {
+                    b'[' => {
+                        self.state = State::Csi;
+                        self.csi.parameters = [0; MAX_PARAMETERS];
+                        self.csi.count = 0;
+                        None
+                    },
+                    b'7' => Some(Action::SaveCursor),
+                    b'8' => Some(Action::RestoreCursor),
+                    _ => None
+                }
+            },
+            State::Csi => match byte {
+                b'0'..=b'9' => {
+                    if self.csi.count == 0 {
+                        self.csi.count = 1;
+                    }
+
+                    if let Some(parameter) = self.csi.parameters.get_mut(self.csi.count - 1) {
+                        *parameter = parameter.saturating_mul(10).saturating_add((byte - b'0') as u16);
+                    }
+
+                    None
+                },
+                b';' => {
+                    // An empty first parameter still counts.
+                    self.csi.count = (self.csi.count.max(1) + 1).min(MAX_PARAMETERS + 1);
+                    None
+                },
+                // Private markers and intermediate bytes, ignored.
+                0x20..=0x3F => None,
+                0x40..=0x7E => {
+                    self.state = State::Ground;
+                    self.csi.count = self.csi.count.min(MAX_PARAMETERS);
+                    self.csi.final_byte = byte;
+
+                    Some(Action::Csi(self.csi))
+                },
+                // Anything else cancels the sequence.
+                _ => {
+                    self.state = State::Ground;
+                    None
+                }
+            }
+        }
+    }
+}
\ No newline at end of file
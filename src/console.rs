@@ -0,0 +1,161 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::future::Future;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use futures_util::stream::StreamExt;
+use lazy_static::lazy_static;
+use spin::{Mutex, MutexGuard};
+use x86_64::instructions::interrupts::without_interrupts;
+use crate::input::{self, Key};
+use crate::thread::{self, ThreadId};
+use crate::vga_buffer::Writer;
+
+// Virtual consoles, switched with Alt+F1 and onwards.
+pub const CONSOLE_COUNT: usize = 6;
+
+// Where output of threads that belong to no console goes, like boot messages.
+pub const KERNEL_CONSOLE: usize = 0;
+
+const KERNEL_LOG_SIZE: usize = 16 * 1024;
+
+lazy_static! {
+    static ref CONSOLES: [Mutex<Writer>; CONSOLE_COUNT] = {
+        let consoles: [Mutex<Writer>; CONSOLE_COUNT] = core::array::from_fn(|_| Mutex::new(Writer::new()));
+        consoles[KERNEL_CONSOLE].lock().activate();
+
+        consoles
+    };
+}
+
+static ACTIVE: AtomicUsize = AtomicUsize::new(KERNEL_CONSOLE);
+
+// Threads whose output goes to a console of their own.
+static ATTACHED: Mutex<BTreeMap<ThreadId, usize>> = Mutex::new(BTreeMap::new());
+
+// Everything printed to the kernel console, for dmesg. It lives outside the heap, as
+// the first messages come before it exists.
+static KERNEL_LOG: Mutex<KernelLog> = Mutex::new(KernelLog {
+    bytes: [0; KERNEL_LOG_SIZE],
+    start: 0,
+    length: 0
+});
+
+// The writer of the console the running thread prints to.
+pub static WRITER: CurrentWriter = CurrentWriter { _private: () };
+
+pub struct CurrentWriter {
+    _private: ()
+}
+
+impl CurrentWriter {
+    pub fn lock(&self) -> MutexGuard<'static, Writer> {
+        writer(current()).lock()
+    }
+}
+
+struct KernelLog {
+    bytes: [u8; KERNEL_LOG_SIZE],
+    start: usize,
+    length: usize
+}
+
+impl fmt::Write for KernelLog {
+    // Overwrites the oldest bytes once full.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            let end = (self.start + self.length) % KERNEL_LOG_SIZE;
+            self.bytes[end] = byte;
+
+            if self.length < KERNEL_LOG_SIZE {
+                self.length += 1;
+            } else {
+                self.start = (self.start + 1) % KERNEL_LOG_SIZE;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub fn writer(index: usize) -> &'static Mutex<Writer> {
+    &CONSOLES[index]
+}
+
+pub fn active() -> usize {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+// The console the running thread is attached to, if any.
+pub fn attached() -> Option<usize> {
+    let id = thread::current()?;
+
+    without_interrupts(|| ATTACHED.lock().get(&id).copied())
+}
+
+pub fn current() -> usize {
+    attached().unwrap_or(KERNEL_CONSOLE)
+}
+
+// Sends the running thread's output to the console from now on.
+pub fn attach(index: usize) {
+    if let Some(id) = thread::current() {
+        without_interrupts(|| ATTACHED.lock().insert(id, index));
+    }
+}
+
+pub fn switch(index: usize) {
+    without_interrupts(|| {
+        let previous = ACTIVE.swap(index, Ordering::Relaxed);
+
+        if previous != index {
+            CONSOLES[previous].lock().deactivate();
+            CONSOLES[index].lock().activate();
+        }
+    });
+}
+
+// Fits every console to the screen after its size changed.
+pub fn resize() {
+    without_interrupts(|| {
+        for console in CONSOLES.iter() {
+            console.lock().resize();
+        }
+    });
+}
+
+pub fn log(args: fmt::Arguments) {
+    use core::fmt::Write;
+
+    without_interrupts(|| {
+        KERNEL_LOG.lock().write_fmt(args).unwrap();
+    });
+}
+
+pub fn kernel_log() -> String {
+    let bytes: Vec<u8> = without_interrupts(|| {
+        let log = KERNEL_LOG.lock();
+
+        (0..log.length).map(|offset| log.bytes[(log.start + offset) % KERNEL_LOG_SIZE]).collect()
+    });
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+// Switches consoles on Alt+F1 to Alt+F6.
+pub fn input_handler() -> impl Future<Output = ()> {
+    let mut events = input::subscribe();
+
+    async move {
+        while let Some(event) = events.next().await {
+            if let Key::Function(number) = event.key {
+                let index = number as usize;
+
+                if event.is_pressed() && event.modifiers.alt && 1 <= index && index <= CONSOLE_COUNT {
+                    switch(index - 1);
+                }
+            }
+        }
+    }
+}
\ No newline at end of file
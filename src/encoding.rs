@@ -0,0 +1,138 @@
+use core::sync::atomic::{AtomicU8, Ordering};
+
+// What the VGA font shows for characters it has no glyph for.
+static FALLBACK_GLYPH: AtomicU8 = AtomicU8::new(0xFE);
+
+// The VGA font follows code page 437. Bytes 0x01 to 0x1F and 0x7F are glyphs too, but
+// only reachable through these characters, as the bytes themselves are control codes.
+const LOW_GLYPHS: [char; 31] = [
+    '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼',
+    '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼'
+];
+
+const HOUSE: char = '⌂';
+
+const HIGH_GLYPHS: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}'
+];
+
+// Characters the font lacks but has a close enough glyph for, like the Polish letters
+// shown without their diacritics.
+const APPROXIMATIONS: [(char, u8); 29] = [
+    ('ą', b'a'), ('ć', b'c'), ('ę', b'e'), ('ł', b'l'), ('ń', b'n'), ('ś', b's'), ('ź', b'z'), ('ż', b'z'),
+    ('Ą', b'A'), ('Ć', b'C'), ('Ę', b'E'), ('Ł', b'L'), ('Ń', b'N'), ('Ó', b'O'), ('Ś', b'S'), ('Ź', b'Z'), ('Ż', b'Z'),
+    ('‘', b'\''), ('’', b'\''), ('“', b'"'), ('”', b'"'), ('–', b'-'), ('—', b'-'), ('…', 0xFA),
+    ('β', 0xE1), ('μ', 0xE6), ('∑', 0xE4), ('∈', 0xEE), ('⋅', 0xFA)
+];
+
+pub fn fallback_glyph() -> u8 {
+    FALLBACK_GLYPH.load(Ordering::Relaxed)
+}
+
+pub fn set_fallback_glyph(glyph: u8) {
+    FALLBACK_GLYPH.store(glyph, Ordering::Relaxed);
+}
+
+// The code page 437 byte showing this character, if the font has it.
+pub fn to_cp437(character: char) -> Option<u8> {
+    match character {
+        ' '..='~' => return Some(character as u8),
+        HOUSE => return Some(0x7F),
+        _ => { }
+    }
+
+    if let Some(index) = LOW_GLYPHS.iter().position(|glyph| *glyph == character) {
+        return Some(index as u8 + 0x01);
+    }
+
+    if let Some(index) = HIGH_GLYPHS.iter().position(|glyph| *glyph == character) {
+        return Some(index as u8 + 0x80);
+    }
+
+    APPROXIMATIONS.iter().find(|(approximated, _)| *approximated == character).map(|(_, glyph)| *glyph)
+}
+
+// Like to_cp437, with the fallback glyph for characters the font can't show.
+pub fn encode(character: char) -> u8 {
+    to_cp437(character).unwrap_or_else(fallback_glyph)
+}
+
+// The character a glyph on screen stands for.
+pub fn from_cp437(byte: u8) -> char {
+    match byte {
+        0x00 => ' ',
+        0x01..=0x1F => LOW_GLYPHS[byte as usize - 0x01],
+        0x7F => HOUSE,
+        0x80..=0xFF => HIGH_GLYPHS[byte as usize - 0x80],
+        _ => byte as char
+    }
+}
+
+// Turns a byte stream back into characters, even when a character's bytes arrive in
+// separate writes. Invalid sequences come out as U+FFFD.
+pub struct Utf8Decoder {
+    code_point: u32,
+    remaining: u8,
+    // The smallest code point the sequence may encode, to reject overlong forms.
+    minimum: u32
+}
+
+impl Utf8Decoder {
+    pub const fn new() -> Utf8Decoder {
+        Utf8Decoder {
+            code_point: 0,
+            remaining: 0,
+            minimum: 0
+        }
+    }
+
+    // Gives up to two characters, as a byte breaking off a sequence leaves U+FFFD for the
+    // unfinished character before whatever the byte itself decodes to.
+    pub fn advance(&mut self, byte: u8) -> [Option<char>; 2] {
+        if self.remaining != 0 && byte & 0b1100_0000 != 0b1000_0000 {
+            self.remaining = 0;
+
+            return [Some(char::REPLACEMENT_CHARACTER), self.decode(byte)];
+        }
+
+        [self.decode(byte), None]
+    }
+
+    fn decode(&mut self, byte: u8) -> Option<char> {
+        if self.remaining != 0 {
+            self.code_point = self.code_point << 6 | (byte & 0b0011_1111) as u32;
+            self.remaining -= 1;
+
+            if self.remaining != 0 {
+                return None;
+            }
+
+            if self.code_point < self.minimum {
+                return Some(char::REPLACEMENT_CHARACTER);
+            }
+
+            return Some(char::from_u32(self.code_point).unwrap_or(char::REPLACEMENT_CHARACTER));
+        }
+
+        let (remaining, bits, minimum) = match byte {
+            0x00..=0x7F => return Some(byte as char),
+            0xC0..=0xDF => (1, byte & 0b0001_1111, 0x80),
+            0xE0..=0xEF => (2, byte & 0b0000_1111, 0x800),
+            0xF0..=0xF7 => (3, byte & 0b0000_0111, 0x10000),
+            _ => return Some(char::REPLACEMENT_CHARACTER)
+        };
+
+        self.code_point = bits as u32;
+        self.remaining = remaining;
+        self.minimum = minimum;
+
+        None
+    }
+}
\ No newline at end of file
@@ -0,0 +1,244 @@
+use conquer_once::spin::OnceCell;
+use spin::Mutex;
+use x86_64::VirtAddr;
+use crate::psf::Font;
+use crate::vga_buffer::{self, CursorStyle};
+
+// Mode 13h, which the bootloader switches to when the kernel is built with the graphics
+// feature. One byte per pixel, indexing the palette.
+const MODE_13H_ADDRESS: u64 = 0xA0000;
+const MODE_13H_WIDTH: usize = 320;
+const MODE_13H_HEIGHT: usize = 200;
+
+// The 16 text colours as 0xRRGGBB, for framebuffers without a palette. The default VGA
+// palette starts with the same ones.
+const TEXT_COLORS: [u32; 16] = [
+    0x000000, 0x0000AA, 0x00AA00, 0x00AAAA, 0xAA0000, 0xAA00AA, 0xAA5500, 0xAAAAAA,
+    0x555555, 0x5555FF, 0x55FF55, 0x55FFFF, 0xFF5555, 0xFF55FF, 0xFFFF55, 0xFFFFFF
+];
+
+static TEXT_CONSOLE: OnceCell<Mutex<TextConsole>> = OnceCell::uninit();
+
+// A linear framebuffer. Pixel values are palette indices for 8 bits per pixel and
+// 0xRRGGBB for more. Drawing outside of it is clipped.
+pub struct Framebuffer {
+    address: *mut u8,
+    pub width: usize,
+    pub height: usize,
+    // Bytes from one row of pixels to the next.
+    stride: usize,
+    bytes_per_pixel: usize
+}
+
+// Only ever reached through the text console's lock.
+unsafe impl Send for Framebuffer { }
+
+impl Framebuffer {
+    // The memory has to be mapped and used for nothing else.
+    pub unsafe fn new(address: VirtAddr, width: usize, height: usize, stride: usize, bytes_per_pixel: usize) -> Framebuffer {
+        Framebuffer {
+            address: address.as_mut_ptr(),
+            width,
+            height,
+            stride,
+            bytes_per_pixel
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // The pixel value for one of the 16 text colours.
+    pub fn color(&self, color: u8) -> u32 {
+        if self.bytes_per_pixel == 1 {
+            color as u32
+        } else {
+            TEXT_COLORS[color as usize & 0xF]
+        }
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, value: u32) {
+        if self.width <= x || self.height <= y {
+            return;
+        }
+
+        unsafe {
+            let pixel = self.address.add(y * self.stride + x * self.bytes_per_pixel);
+
+            match self.bytes_per_pixel {
+                1 => pixel.write_volatile(value as u8),
+                2 => (pixel as *mut u16).write_volatile(value as u16),
+                3 => {
+                    for byte in 0..3 {
+                        pixel.add(byte).write_volatile((value >> (byte * 8)) as u8);
+                    }
+                },
+                _ => (pixel as *mut u32).write_volatile(value)
+            }
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Option<u32> {
+        if self.width <= x || self.height <= y {
+            return None;
+        }
+
+        unsafe {
+            let pixel = self.address.add(y * self.stride + x * self.bytes_per_pixel);
+
+            Some(match self.bytes_per_pixel {
+                1 => pixel.read_volatile() as u32,
+                2 => (pixel as *const u16).read_volatile() as u32,
+                3 => (0..3).fold(0, |value, byte| value | (pixel.add(byte).read_volatile() as u32) << (byte * 8)),
+                _ => (pixel as *const u32).read_volatile()
+            })
+        }
+    }
+
+    // Bresenham's line, both ends included. The ends may lie off screen.
+    pub fn line(&mut self, x0: isize, y0: isize, x1: isize, y1: isize, value: u32) {
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let step_x = if x0 < x1 { 1 } else { -1 };
+        let step_y = if y0 < y1 { 1 } else { -1 };
+
+        let mut error = dx + dy;
+        let (mut x, mut y) = (x0, y0);
+
+        loop {
+            if 0 <= x && 0 <= y {
+                self.set_pixel(x as usize, y as usize, value);
+            }
+
+            if x == x1 && y == y1 {
+                break;
+            }
+
+            let doubled = 2 * error;
+
+            if dy <= doubled {
+                error += dy;
+                x += step_x;
+            }
+
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    // Clipped to the screen.
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, value: u32) {
+        let right = x.saturating_add(width).min(self.width);
+        let bottom = y.saturating_add(height).min(self.height);
+
+        for row in y..bottom {
+            for column in x..right {
+                self.set_pixel(column, row, value);
+            }
+        }
+    }
+
+    // Just the outline.
+    pub fn rect(&mut self, x: usize, y: usize, width: usize, height: usize, value: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        self.fill_rect(x, y, width, 1, value);
+        self.fill_rect(x, y.saturating_add(height - 1), width, 1, value);
+        self.fill_rect(x, y, 1, height, value);
+        self.fill_rect(x.saturating_add(width - 1), y, 1, height, value);
+    }
+
+    // Copies pixels given row by row, each row `width` pixels long.
+    pub fn blit(&mut self, x: usize, y: usize, width: usize, pixels: &[u32]) {
+        if width == 0 {
+            return;
+        }
+
+        for (index, value) in pixels.iter().enumerate() {
+            self.set_pixel(x.saturating_add(index % width), y.saturating_add(index / width), *value);
+        }
+    }
+}
+
+// Draws the text of the console on screen with a bitmap font, when there is no text
+// mode to do it.
+pub struct TextConsole {
+    framebuffer: Framebuffer,
+    font: Font
+}
+
+impl TextConsole {
+    pub fn new(framebuffer: Framebuffer, font: Font) -> TextConsole {
+        TextConsole {
+            framebuffer,
+            font
+        }
+    }
+
+    // As many characters as fit on the screen.
+    pub fn columns(&self) -> usize {
+        self.framebuffer.width / self.font.width
+    }
+
+    pub fn rows(&self) -> usize {
+        self.framebuffer.height / self.font.height
+    }
+
+    pub fn framebuffer(&mut self) -> &mut Framebuffer {
+        &mut self.framebuffer
+    }
+
+    pub fn draw_cell(&mut self, row: usize, column: usize, glyph: u8, foreground: u8, background: u8, cursor: Option<CursorStyle>) {
+        let foreground = self.framebuffer.color(foreground);
+        let background = self.framebuffer.color(background);
+
+        let (width, height) = (self.font.width, self.font.height);
+
+        let cursor_rows = match cursor {
+            Some(CursorStyle::Block) => height,
+            Some(CursorStyle::Underline) => height.min(2),
+            None => 0
+        };
+
+        for y in 0..height {
+            for x in 0..width {
+                let inverted = height - cursor_rows <= y;
+                let value = if self.font.is_set(glyph, x, y) != inverted { foreground } else { background };
+
+                self.framebuffer.set_pixel(column * width + x, row * height + y, value);
+            }
+        }
+    }
+}
+
+// Takes over the framebuffer the bootloader set up, if it was built for graphics.
+// Console output is drawn there from then on.
+pub fn init(physical_memory_offset: VirtAddr) {
+    if !cfg!(feature = "graphics") {
+        return;
+    }
+
+    let framebuffer = unsafe {
+        Framebuffer::new(physical_memory_offset + MODE_13H_ADDRESS, MODE_13H_WIDTH, MODE_13H_HEIGHT, MODE_13H_WIDTH, 1)
+    };
+
+    let text_console = TextConsole::new(framebuffer, Font::default());
+
+    vga_buffer::set_size(text_console.columns(), text_console.rows(), text_console.font.height as u8);
+
+    TEXT_CONSOLE.try_init_once(|| Mutex::new(text_console))
+        .expect("Framebuffer should be initialized only once.");
+}
+
+pub fn text_console() -> Option<&'static Mutex<TextConsole>> {
+    TEXT_CONSOLE.try_get().ok()
+}
\ No newline at end of file
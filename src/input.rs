@@ -0,0 +1,193 @@
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+use lazy_static::lazy_static;
+use pc_keyboard::KeyCode;
+use spin::Mutex;
+use crate::task::sync::mpsc::{self, Receiver, Sender, TrySendError};
+
+// Events buffered per subscriber before new ones get dropped.
+pub const SUBSCRIBER_QUEUE_SIZE: usize = 64;
+
+lazy_static! {
+    static ref SUBSCRIBERS: Mutex<Vec<Sender<KeyEvent>>> = Mutex::new(Vec::new());
+}
+
+lazy_static! {
+    static ref MOUSE_SUBSCRIBERS: Mutex<Vec<Sender<MouseEvent>>> = Mutex::new(Vec::new());
+}
+
+static DROPPED_EVENTS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Tab,
+    Escape,
+    Delete,
+    Insert,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    ArrowLeft,
+    ArrowRight,
+    ArrowUp,
+    ArrowDown,
+    Function(u8),
+    Shift,
+    Control,
+    Alt,
+    AltGr,
+    CapsLock,
+    NumLock,
+    ScrollLock,
+    Other(KeyCode)
+}
+
+impl Key {
+    // Keys whose meaning doesn't depend on the layout or modifiers.
+    pub fn from_code(code: KeyCode) -> Option<Key> {
+        let key = match code {
+            KeyCode::Enter | KeyCode::NumpadEnter => Key::Enter,
+            KeyCode::Backspace => Key::Backspace,
+            KeyCode::Tab => Key::Tab,
+            KeyCode::Escape => Key::Escape,
+            KeyCode::Delete => Key::Delete,
+            KeyCode::Insert => Key::Insert,
+            KeyCode::Home => Key::Home,
+            KeyCode::End => Key::End,
+            KeyCode::PageUp => Key::PageUp,
+            KeyCode::PageDown => Key::PageDown,
+            KeyCode::ArrowLeft => Key::ArrowLeft,
+            KeyCode::ArrowRight => Key::ArrowRight,
+            KeyCode::ArrowUp => Key::ArrowUp,
+            KeyCode::ArrowDown => Key::ArrowDown,
+            KeyCode::F1 => Key::Function(1),
+            KeyCode::F2 => Key::Function(2),
+            KeyCode::F3 => Key::Function(3),
+            KeyCode::F4 => Key::Function(4),
+            KeyCode::F5 => Key::Function(5),
+            KeyCode::F6 => Key::Function(6),
+            KeyCode::F7 => Key::Function(7),
+            KeyCode::F8 => Key::Function(8),
+            KeyCode::F9 => Key::Function(9),
+            KeyCode::F10 => Key::Function(10),
+            KeyCode::F11 => Key::Function(11),
+            KeyCode::F12 => Key::Function(12),
+            KeyCode::ShiftLeft | KeyCode::ShiftRight => Key::Shift,
+            KeyCode::ControlLeft | KeyCode::ControlRight => Key::Control,
+            KeyCode::AltLeft => Key::Alt,
+            KeyCode::AltRight => Key::AltGr,
+            KeyCode::CapsLock => Key::CapsLock,
+            KeyCode::NumpadLock => Key::NumLock,
+            KeyCode::ScrollLock => Key::ScrollLock,
+            _ => return None
+        };
+
+        Some(key)
+    }
+
+    // Maps control characters produced by the layout to their named keys.
+    pub fn from_char(character: char) -> Key {
+        match character {
+            '\n' | '\r' => Key::Enter,
+            '\u{8}' => Key::Backspace,
+            '\t' => Key::Tab,
+            '\u{1b}' => Key::Escape,
+            '\u{7f}' => Key::Delete,
+            character => Key::Char(character)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub alt_gr: bool,
+    pub caps_lock: bool,
+    pub num_lock: bool,
+    pub scroll_lock: bool
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyState {
+    Pressed,
+    Released
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub code: KeyCode,
+    pub key: Key,
+    pub modifiers: Modifiers,
+    pub state: KeyState
+}
+
+impl KeyEvent {
+    pub fn is_pressed(&self) -> bool {
+        self.state == KeyState::Pressed
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MouseButtons {
+    pub left: bool,
+    pub right: bool,
+    pub middle: bool
+}
+
+// Movement is relative, in mouse counts. dy grows downwards like screen rows, wheel is
+// negative when scrolled away from the user and always 0 on mice without one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseEvent {
+    pub dx: i16,
+    pub dy: i16,
+    pub wheel: i8,
+    pub buttons: MouseButtons
+}
+
+// Every subscriber gets its own copy of all key events from now on. Dropping the
+// receiver unsubscribes.
+pub fn subscribe() -> Receiver<KeyEvent> {
+    let (sender, receiver) = mpsc::channel(SUBSCRIBER_QUEUE_SIZE);
+    SUBSCRIBERS.lock().push(sender);
+
+    receiver
+}
+
+pub fn publish(event: KeyEvent) {
+    publish_to(&SUBSCRIBERS, event);
+}
+
+// Same as subscribe, for mouse events.
+pub fn subscribe_mouse() -> Receiver<MouseEvent> {
+    let (sender, receiver) = mpsc::channel(SUBSCRIBER_QUEUE_SIZE);
+    MOUSE_SUBSCRIBERS.lock().push(sender);
+
+    receiver
+}
+
+pub fn publish_mouse(event: MouseEvent) {
+    publish_to(&MOUSE_SUBSCRIBERS, event);
+}
+
+fn publish_to<T: Copy>(subscribers: &Mutex<Vec<Sender<T>>>, event: T) {
+    subscribers.lock().retain(|subscriber| {
+        match subscriber.try_send(event) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                DROPPED_EVENTS.fetch_add(1, Ordering::Relaxed);
+                true
+            },
+            Err(TrySendError::Closed(_)) => false
+        }
+    });
+}
+
+pub fn dropped_events() -> u64 {
+    DROPPED_EVENTS.load(Ordering::Relaxed)
+}
\ No newline at end of file
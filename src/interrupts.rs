@@ -1,12 +1,12 @@
 use lazy_static::lazy_static;
-use pc_keyboard::{layouts, HandleControl, Keyboard, ScancodeSet1};
-use spin::Mutex;
 use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
-use crate::{gdt, println, ColorCode, Color, hlt_loop, vga_buffer::WRITER};
+use crate::{gdt, println, Color, hlt_loop, console::WRITER};
 use pic8259::ChainedPics;
 use x86_64::instructions::port::Port;
 use x86_64::structures::idt::PageFaultErrorCode;
 use x86_64::registers::control::Cr2;
+use x86_64::VirtAddr;
+use crate::thread;
 
 pub fn init() {
     IDT.load();
@@ -29,8 +29,13 @@ lazy_static! {
             .set_stack_index(gdt::DOUBLE_FAULT_IST_INDEX);
         }
 
-        idt[InterruptIndex::Timer.as_usize()].set_handler_fn(timer_interrupt_handler);
+        unsafe {
+            idt[InterruptIndex::Timer.as_usize()].set_handler_addr(VirtAddr::new(thread::timer_interrupt_entry as unsafe extern "C" fn() as u64));
+            idt[thread::YIELD_INTERRUPT as usize].set_handler_addr(VirtAddr::new(thread::yield_interrupt_entry as unsafe extern "C" fn() as u64));
+        }
+
         idt[InterruptIndex::Keyboard.as_usize()].set_handler_fn(keyboard_interrupt_handler);
+        idt[InterruptIndex::Mouse.as_usize()].set_handler_fn(mouse_interrupt_handler);
 
         idt
     };
@@ -42,7 +47,7 @@ extern "x86-interrupt" fn empty_handler(_stack_frame: InterruptStackFrame) {
 
 extern "x86-interrupt" fn page_fault_handler(stack_frame: InterruptStackFrame, error_code: PageFaultErrorCode) {
     x86_64::instructions::interrupts::without_interrupts(|| {
-        WRITER.lock().change_color_code(ColorCode::new(Color::LightRed, Color::Black));
+        WRITER.lock().change_foreground(Color::LightRed);
     });
 
     println!("EXCEPTION: PAGE FAULT");
@@ -55,13 +60,13 @@ extern "x86-interrupt" fn page_fault_handler(stack_frame: InterruptStackFrame, e
 
 extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame) {
     x86_64::instructions::interrupts::without_interrupts(|| {
-        WRITER.lock().change_color_code(ColorCode::new(Color::LightRed, Color::Black));
+        WRITER.lock().change_foreground(Color::LightRed);
     });
 
     println!("EXCEPTION: BREAKPOINT\n{:3?}", stack_frame);
 
     x86_64::instructions::interrupts::without_interrupts(|| {
-        WRITER.lock().change_color_code(ColorCode::new(Color::White, Color::Black));
+        WRITER.lock().reset_color_code();
     });
 }
 
@@ -69,17 +74,15 @@ extern "x86-interrupt" fn double_fault_handler(stack_frame: InterruptStackFrame,
     panic!("EXCEPTION: DOUBLE FAULT\nCODE:{}\n{:#?}", error_code, stack_frame);
 }
 
-extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFrame) {
+// The timer interrupt itself enters through thread::timer_interrupt_entry, which
+// switches threads after acknowledging it here.
+pub fn end_of_timer_interrupt() {
     unsafe {
         PICS.lock().notify_end_of_interrupt(InterruptIndex::Timer.as_u8());
     }
 }
 
 extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStackFrame) {
-    lazy_static! {
-        static ref KEYBOARD: Mutex<Keyboard<layouts::Us104Key, ScancodeSet1>> = Mutex::new(Keyboard::new(layouts::Us104Key, ScancodeSet1, HandleControl::Ignore));
-    }
-
     let mut port = Port::new(0x60);
     let scancode: u8 = unsafe { port.read() };
 
@@ -90,11 +93,24 @@ extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStac
     }
 }
 
+extern "x86-interrupt" fn mouse_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    let mut port = Port::new(0x60);
+    let byte: u8 = unsafe { port.read() };
+
+    crate::task::mouse::add_byte(byte);
+
+    unsafe {
+        PICS.lock().notify_end_of_interrupt(InterruptIndex::Mouse.as_u8());
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 #[repr(u8)]
 pub enum InterruptIndex {
     Timer = PIC_1_OFFSET,
-    Keyboard
+    Keyboard,
+    // IRQ12, on the second PIC.
+    Mouse = PIC_2_OFFSET + 4
 }
 
 impl InterruptIndex {
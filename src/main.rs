@@ -9,13 +9,25 @@
 #![no_main]
 
 mod vga_buffer;
+mod console;
+mod ansi;
+mod encoding;
+mod framebuffer;
+mod psf;
+mod text_mode;
 mod interrupts;
 mod gdt;
 mod memory;
 mod allocator;
 mod shell;
-mod reading;
 mod task;
+mod thread;
+mod input;
+mod ps2;
+mod pointer;
+mod screen;
+mod rtc;
+mod status;
 
 use core::arch::asm;
 use core::panic::PanicInfo;
@@ -25,8 +37,10 @@ use x86_64::VirtAddr;
 use crate::memory::BootInfoFrameAllocator;
 use crate::task::executor::Executor;
 use crate::task::keyboard::SCANCODE_QUEUE;
-use crate::task::{keyboard, Task};
-use crate::vga_buffer::{Color, ColorCode, WRITER};
+use crate::task::mouse::MOUSE_QUEUE;
+use crate::task::{keyboard, mouse, Priority};
+use crate::console::WRITER;
+use crate::vga_buffer::{Color, ColorCode, CursorStyle};
 
 extern crate alloc;
 
@@ -35,6 +49,13 @@ entry_point!(kernel_main);
 pub static OS_VERSION: &str = "1.0";
 
 fn kernel_main(boot_info: &'static BootInfo) -> ! {
+    vga_buffer::init(VirtAddr::new(boot_info.physical_memory_offset));
+    framebuffer::init(VirtAddr::new(boot_info.physical_memory_offset));
+
+    if framebuffer::text_console().is_none() {
+        text_mode::disable_blink();
+    }
+
     println!("Starting kernel");
 
     gdt::init();
@@ -57,36 +78,52 @@ fn kernel_main(boot_info: &'static BootInfo) -> ! {
 
     println!("[HEAP] Initialized");
 
-    SCANCODE_QUEUE.try_init_once(|| ArrayQueue::new(100)).expect("Scancode Queue should be initialized only once.");
+    SCANCODE_QUEUE.try_init_once(|| ArrayQueue::new(keyboard::SCANCODE_QUEUE_SIZE)).expect("Scancode Queue should be initialized only once.");
     println!("[SCANCODE QUEUE] Initialized");
 
-    unsafe {
-        // Disable VGA cursor
-        let mut port: u16 = 0x3D4;
-        let mut value: u8 = 0xA;
+    MOUSE_QUEUE.try_init_once(|| ArrayQueue::new(mouse::MOUSE_QUEUE_SIZE)).expect("Mouse Queue should be initialized only once.");
 
-        asm!("out dx, al", in("dx") port, in("al") value, options(nomem, nostack, preserves_flags));
+    let has_mouse = mouse::init();
 
-        port = 0x3D5;
-        value = 0x20;
-
-        asm!("out dx, al", in("dx") port, in("al") value, options(nomem, nostack, preserves_flags));
+    if has_mouse {
+        println!("[MOUSE] Initialized");
+    } else {
+        println!("[MOUSE] Not found");
     }
 
+    thread::init();
+    println!("[THREADS] Initialized");
+
     x86_64::instructions::interrupts::without_interrupts(|| {
-        WRITER.lock().clear();
+        let mut writer = WRITER.lock();
+        writer.set_cursor_style(CursorStyle::Block);
+        writer.clear();
     });
 
     println!("Ready");
 
     WRITER.lock().clear();
 
-    shell::print_info_message();
-    shell::initial_run();
+    shell::start();
 
     let mut executor = Executor::new();
 
-    executor.spawn(Task::new(keyboard::input_handler()));
+    task::SPAWNER.try_init_once(|| executor.spawner()).expect("Spawner should be initialized only once.");
+
+    // Input is interrupt driven and should stay responsive whatever else is running.
+    task::spawn_prioritized("keyboard", Priority::High, keyboard::handle_scancodes());
+    task::spawn_prioritized("console", Priority::High, console::input_handler());
+
+    for index in 0..console::CONSOLE_COUNT {
+        task::spawn_prioritized("prompt", Priority::High, shell::prompt::input_handler(index));
+    }
+
+    if has_mouse {
+        task::spawn_prioritized("mouse", Priority::High, mouse::handle_packets());
+        task::spawn_named("pointer", pointer::input_handler());
+    }
+
+    task::spawn_prioritized("status", Priority::Low, status::run());
 
     executor.run();
 }
@@ -102,7 +139,7 @@ fn hlt_loop() -> ! {
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
     x86_64::instructions::interrupts::without_interrupts(|| {
-        WRITER.lock().change_color_code(ColorCode::new(Color::LightRed, Color::Black));
+        WRITER.lock().change_foreground(Color::LightRed);
     });
 
     println!("{}", info);
@@ -112,25 +149,13 @@ fn panic(info: &PanicInfo) -> ! {
 
 fn error(message: &str) {
     x86_64::instructions::interrupts::without_interrupts(|| {
-        WRITER.lock().change_color_code(ColorCode::new(Color::LightRed, Color::Black));
+        WRITER.lock().change_foreground(Color::LightRed);
     });
 
     println!("ERROR: {}", message);
 
     x86_64::instructions::interrupts::without_interrupts(|| {
-        WRITER.lock().change_color_code(ColorCode::new(Color::White, Color::Black));
-    });
-}
-
-fn warning(message: &str) {
-    x86_64::instructions::interrupts::without_interrupts(|| {
-        WRITER.lock().change_color_code(ColorCode::new(Color::Yellow, Color::Black));
-    });
-
-    println!("{}", message);
-
-    x86_64::instructions::interrupts::without_interrupts(|| {
-        WRITER.lock().change_color_code(ColorCode::new(Color::White, Color::Black));
+        WRITER.lock().reset_color_code();
     });
 }
 
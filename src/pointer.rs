@@ -0,0 +1,81 @@
+use alloc::string::String;
+use core::future::Future;
+use futures_util::stream::StreamExt;
+use lazy_static::lazy_static;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+use crate::input::{self, MouseButtons};
+use crate::console;
+use crate::vga_buffer;
+
+// Mouse counts per text cell, about what a character takes up on a 640x400 screen.
+const CELL_WIDTH: i32 = 8;
+const CELL_HEIGHT: i32 = 16;
+
+// Lines scrolled per wheel notch.
+const WHEEL_LINES: isize = 3;
+
+lazy_static! {
+    static ref PASTE_BUFFER: Mutex<String> = Mutex::new(String::new());
+}
+
+// Whatever was selected with the mouse last.
+pub fn paste_buffer() -> String {
+    PASTE_BUFFER.lock().clone()
+}
+
+// Moves the pointer cell around the console on screen, scrolls back with the wheel and
+// copies text selected by dragging with the left button into the paste buffer.
+pub fn input_handler() -> impl Future<Output = ()> {
+    let mut events = input::subscribe_mouse();
+
+    async move {
+        let mut x = vga_buffer::width() as i32 * CELL_WIDTH / 2;
+        let mut y = vga_buffer::height() as i32 * CELL_HEIGHT / 2;
+        let mut buttons = MouseButtons::default();
+
+        // Where the left button went down, while it is held.
+        let mut anchor: Option<(usize, usize)> = None;
+
+        while let Some(event) = events.next().await {
+            // The screen may have changed size since the last event.
+            let width = vga_buffer::width() as i32 * CELL_WIDTH;
+            let height = vga_buffer::height() as i32 * CELL_HEIGHT;
+
+            x = (x + event.dx as i32).max(0).min(width - 1);
+            y = (y + event.dy as i32).max(0).min(height - 1);
+
+            let cell = ((y / CELL_HEIGHT) as usize, (x / CELL_WIDTH) as usize);
+
+            let copied = without_interrupts(|| {
+                let mut writer = console::writer(console::active()).lock();
+                let mut copied = None;
+
+                if event.wheel != 0 {
+                    writer.scroll_view(-(event.wheel as isize) * WHEEL_LINES);
+                }
+
+                if event.buttons.left && !buttons.left {
+                    writer.clear_selection();
+                    anchor = Some(cell);
+                } else if event.buttons.left {
+                    if let Some(anchor) = anchor {
+                        writer.select(anchor, cell);
+                    }
+                } else if buttons.left && anchor.take().map_or(false, |anchor| anchor != cell) {
+                    copied = Some(writer.selected_text());
+                }
+
+                writer.show_pointer(cell.0, cell.1);
+
+                copied
+            });
+
+            if let Some(text) = copied {
+                *PASTE_BUFFER.lock() = text;
+            }
+
+            buttons = event.buttons;
+        }
+    }
+}
\ No newline at end of file
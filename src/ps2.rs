@@ -0,0 +1,80 @@
+use x86_64::instructions::port::Port;
+
+// 8042 PS/2 controller, which both the keyboard and the mouse sit behind.
+const DATA_PORT: u16 = 0x60;
+const STATUS_PORT: u16 = 0x64;
+
+const OUTPUT_FULL: u8 = 0b1;
+const INPUT_FULL: u8 = 0b10;
+
+const READ_CONFIG: u8 = 0x20;
+const WRITE_CONFIG: u8 = 0x60;
+const ENABLE_AUX: u8 = 0xA8;
+const WRITE_AUX: u8 = 0xD4;
+
+pub const ACKNOWLEDGE: u8 = 0xFA;
+pub const RESEND: u8 = 0xFE;
+
+// Polls before giving up, so a missing controller or device doesn't hang the kernel.
+const TIMEOUT: usize = 100_000;
+
+fn status() -> u8 {
+    let mut port: Port<u8> = Port::new(STATUS_PORT);
+    unsafe { port.read() }
+}
+
+fn wait_for_input_empty() {
+    for _ in 0..TIMEOUT {
+        if status() & INPUT_FULL == 0 {
+            return;
+        }
+    }
+}
+
+pub fn write_data(byte: u8) {
+    wait_for_input_empty();
+
+    let mut port: Port<u8> = Port::new(DATA_PORT);
+    unsafe { port.write(byte) };
+}
+
+pub fn write_command(command: u8) {
+    wait_for_input_empty();
+
+    let mut port: Port<u8> = Port::new(STATUS_PORT);
+    unsafe { port.write(command) };
+}
+
+// Only for use with interrupts disabled, otherwise the interrupt handlers take the byte.
+pub fn read_data() -> Option<u8> {
+    for _ in 0..TIMEOUT {
+        if status() & OUTPUT_FULL != 0 {
+            let mut port: Port<u8> = Port::new(DATA_PORT);
+            return Some(unsafe { port.read() });
+        }
+    }
+
+    None
+}
+
+pub fn enable_aux() {
+    write_command(ENABLE_AUX);
+}
+
+pub fn read_config() -> Option<u8> {
+    write_command(READ_CONFIG);
+    read_data()
+}
+
+pub fn write_config(config: u8) {
+    write_command(WRITE_CONFIG);
+    write_data(config);
+}
+
+// Sends a byte to the mouse and waits for it to be acknowledged.
+pub fn write_aux(byte: u8) -> bool {
+    write_command(WRITE_AUX);
+    write_data(byte);
+
+    read_data() == Some(ACKNOWLEDGE)
+}
\ No newline at end of file
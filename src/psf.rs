@@ -0,0 +1,86 @@
+// PC Screen Font, version 1 or 2, the bitmap font format of the Linux console. Glyphs
+// are looked up by code page 437 byte, like the VGA font, the unicode table is ignored.
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_HEADER_SIZE: usize = 4;
+const PSF1_MODE_512: u8 = 0x01;
+
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xB5, 0x4A, 0x86];
+
+// 8x8, with the printable ASCII characters and the few box drawing and block glyphs
+// the console uses.
+static DEFAULT_FONT: &[u8] = include_bytes!("fonts/default8x8.psf");
+
+pub struct Font {
+    data: &'static [u8],
+    glyphs_offset: usize,
+    glyph_count: usize,
+    glyph_size: usize,
+    pub width: usize,
+    pub height: usize
+}
+
+impl Font {
+    pub fn parse(data: &'static [u8]) -> Option<Font> {
+        let font = if data.starts_with(&PSF1_MAGIC) {
+            let mode = *data.get(2)?;
+            let height = *data.get(3)? as usize;
+
+            Font {
+                data,
+                glyphs_offset: PSF1_HEADER_SIZE,
+                glyph_count: if mode & PSF1_MODE_512 != 0 { 512 } else { 256 },
+                glyph_size: height,
+                width: 8,
+                height
+            }
+        } else if data.starts_with(&PSF2_MAGIC) {
+            let field = |index: usize| -> Option<usize> {
+                let bytes = data.get(index * 4..index * 4 + 4)?;
+                Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+            };
+
+            Font {
+                data,
+                glyphs_offset: field(2)?,
+                glyph_count: field(4)?,
+                glyph_size: field(5)?,
+                width: field(7)?,
+                height: field(6)?
+            }
+        } else {
+            return None;
+        };
+
+        if font.width == 0 || font.height == 0 || font.glyph_size < font.bytes_per_row() * font.height {
+            return None;
+        }
+
+        if data.len() < font.glyphs_offset + font.glyph_count * font.glyph_size {
+            return None;
+        }
+
+        Some(font)
+    }
+
+    pub fn default() -> Font {
+        Font::parse(DEFAULT_FONT).expect("Built in font should be valid.")
+    }
+
+    // Glyphs beyond the end of the font are blank.
+    pub fn is_set(&self, glyph: u8, x: usize, y: usize) -> bool {
+        let glyph = glyph as usize;
+
+        if self.glyph_count <= glyph || self.width <= x || self.height <= y {
+            return false;
+        }
+
+        let offset = self.glyphs_offset + glyph * self.glyph_size + y * self.bytes_per_row() + x / 8;
+
+        self.data[offset] & (0x80 >> (x % 8)) != 0
+    }
+
+    fn bytes_per_row(&self) -> usize {
+        (self.width + 7) / 8
+    }
+}
\ No newline at end of file
@@ -0,0 +1,80 @@
+use x86_64::instructions::port::Port;
+
+// The CMOS real time clock, read for the time of day.
+const CMOS_INDEX: u16 = 0x70;
+const CMOS_DATA: u16 = 0x71;
+
+const SECONDS: u8 = 0x00;
+const MINUTES: u8 = 0x02;
+const HOURS: u8 = 0x04;
+const STATUS_A: u8 = 0x0A;
+const STATUS_B: u8 = 0x0B;
+
+const UPDATE_IN_PROGRESS: u8 = 0x80;
+const BINARY_MODE: u8 = 0x04;
+const HOURS_24: u8 = 0x02;
+const PM: u8 = 0x80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Time {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8
+}
+
+fn read_register(register: u8) -> u8 {
+    let mut index: Port<u8> = Port::new(CMOS_INDEX);
+    let mut data: Port<u8> = Port::new(CMOS_DATA);
+
+    unsafe {
+        index.write(register);
+        data.read()
+    }
+}
+
+fn read_time() -> (u8, u8, u8) {
+    while read_register(STATUS_A) & UPDATE_IN_PROGRESS != 0 { }
+
+    (read_register(HOURS), read_register(MINUTES), read_register(SECONDS))
+}
+
+// The clock's time, usually local time. Read twice until both agree, so an update in
+// between doesn't give a mix of old and new values.
+pub fn now() -> Time {
+    let mut time = read_time();
+
+    loop {
+        let again = read_time();
+
+        if again == time {
+            break;
+        }
+
+        time = again;
+    }
+
+    let (mut hours, mut minutes, mut seconds) = time;
+    let status = read_register(STATUS_B);
+    let pm = hours & PM != 0;
+    hours &= !PM;
+
+    if status & BINARY_MODE == 0 {
+        hours = from_bcd(hours);
+        minutes = from_bcd(minutes);
+        seconds = from_bcd(seconds);
+    }
+
+    if status & HOURS_24 == 0 {
+        hours %= 12;
+
+        if pm {
+            hours += 12;
+        }
+    }
+
+    Time { hours, minutes, seconds }
+}
+
+fn from_bcd(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0F)
+}
\ No newline at end of file
@@ -0,0 +1,162 @@
+use alloc::format;
+use alloc::string::String;
+use futures_util::stream::StreamExt;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+use crate::console;
+use crate::encoding;
+use crate::input::{self, Key, KeyEvent};
+use crate::task::sync::mpsc::Receiver;
+use crate::thread;
+use crate::vga_buffer::{self, ColorCode, Writer};
+
+// Exclusive use of the console a command runs on, for things like an editor or a pager.
+// The shell's screen comes back when it is dropped.
+pub struct FullScreen {
+    console: usize,
+    events: Receiver<KeyEvent>
+}
+
+impl FullScreen {
+    // Takes over the console the running thread prints to.
+    pub fn enter() -> FullScreen {
+        let console = console::current();
+
+        without_interrupts(|| console::writer(console).lock().enter_alternate_screen());
+
+        FullScreen {
+            console,
+            events: input::subscribe()
+        }
+    }
+
+    pub fn canvas(&self) -> Canvas {
+        Canvas {
+            writer: console::writer(self.console)
+        }
+    }
+
+    // Waits for the next key pressed while the console is shown. Keys are raw, nothing
+    // gets echoed, only the ones switching consoles are left out.
+    pub fn read_key(&mut self) -> KeyEvent {
+        loop {
+            let event = thread::block_on(self.events.next()).expect("Key events stopped.");
+
+            if !event.is_pressed() || console::active() != self.console {
+                continue;
+            }
+
+            if let Key::Function(number) = event.key {
+                if event.modifiers.alt && number as usize <= console::CONSOLE_COUNT {
+                    continue;
+                }
+            }
+
+            return event;
+        }
+    }
+
+    // Asks for a line of text on the given row. None when cancelled with Escape or Ctrl-G.
+    pub fn read_line(&mut self, row: usize, label: &str, initial: &str) -> Option<String> {
+        let canvas = self.canvas();
+        let color_code = canvas.default_color_code();
+        let mut line = String::from(initial);
+
+        loop {
+            canvas.fill_row(row, &format!("{}{}", label, line), color_code);
+            canvas.set_cursor(Some((row, label.chars().count() + line.chars().count())));
+
+            let event = self.read_key();
+
+            match event.key {
+                Key::Enter => return Some(line),
+                Key::Escape => return None,
+                Key::Char('g') if event.modifiers.ctrl => return None,
+                Key::Backspace => {
+                    line.pop();
+                },
+                Key::Char(character) if !event.modifiers.ctrl && !event.modifiers.alt => line.push(character),
+                _ => { }
+            }
+        }
+    }
+}
+
+impl Drop for FullScreen {
+    fn drop(&mut self) {
+        without_interrupts(|| console::writer(self.console).lock().leave_alternate_screen());
+    }
+}
+
+// Draws cells straight onto a full-screen console. Anything outside of it is clipped.
+#[derive(Clone, Copy)]
+pub struct Canvas {
+    writer: &'static Mutex<Writer>
+}
+
+impl Canvas {
+    pub fn width(&self) -> usize {
+        vga_buffer::width()
+    }
+
+    pub fn height(&self) -> usize {
+        vga_buffer::height()
+    }
+
+    // The colours chosen with the color command.
+    pub fn default_color_code(&self) -> ColorCode {
+        without_interrupts(|| self.writer.lock().default_color_code())
+    }
+
+    // Writes text over the whole row, blanking whatever is left of it.
+    pub fn fill_row(&self, row: usize, text: &str, color_code: ColorCode) {
+        let used = self.text(row, 0, text, color_code);
+        let width = self.width();
+
+        without_interrupts(|| {
+            let mut writer = self.writer.lock();
+
+            for column in used..width {
+                writer.put(row, column, b' ', color_code);
+            }
+        });
+    }
+
+    pub fn put(&self, row: usize, column: usize, character: char, color_code: ColorCode) {
+        without_interrupts(|| self.writer.lock().put(row, column, encoding::encode(character), color_code));
+    }
+
+    // Writes text on one row, cut off at the edge. Returns the columns it took up.
+    pub fn text(&self, row: usize, column: usize, text: &str, color_code: ColorCode) -> usize {
+        let width = self.width();
+
+        without_interrupts(|| {
+            let mut writer = self.writer.lock();
+            let mut end = column;
+
+            for character in text.chars() {
+                if width <= end {
+                    break;
+                }
+
+                writer.put(row, end, encoding::encode(character), color_code);
+                end += 1;
+            }
+
+            end - column
+        })
+    }
+
+    // None hides the cursor.
+    pub fn set_cursor(&self, position: Option<(usize, usize)>) {
+        without_interrupts(|| {
+            let mut writer = self.writer.lock();
+
+            if let Some((row, column)) = position {
+                writer.move_to(row.min(self.height() - 1), column.min(self.width() - 1));
+            }
+
+            writer.set_cursor_visible(position.is_some());
+        });
+    }
+}
\ No newline at end of file
@@ -25,23 +25,57 @@ impl CommandRunner {
         result.insert(String::from("calc"), Box::new(CalcCommand { }));
         result.insert(String::from("set"), Box::new(SetCommand { }));
         result.insert(String::from("color"), Box::new(ColorCommand { }));
+        result.insert(String::from("tasks"), Box::new(TasksCommand { }));
+        result.insert(String::from("threads"), Box::new(ThreadsCommand { }));
+        result.insert(String::from("kill"), Box::new(KillCommand { }));
+        result.insert(String::from("history"), Box::new(HistoryCommand { }));
+        result.insert(String::from("keymap"), Box::new(KeymapCommand { }));
+        result.insert(String::from("cursor"), Box::new(CursorCommand { }));
+        result.insert(String::from("fallback"), Box::new(FallbackCommand { }));
+        result.insert(String::from("dmesg"), Box::new(DmesgCommand { }));
+        result.insert(String::from("mode"), Box::new(ModeCommand { }));
+        result.insert(String::from("draw"), Box::new(DrawCommand { }));
+        result.insert(String::from("clear"), Box::new(ClearCommand { }));
+        result.insert(String::from("edit"), Box::new(EditCommand { }));
+        result.insert(String::from("more"), Box::new(MoreCommand { }));
         result.insert(String::from("help"), Box::new(HelpCommand { }));
 
         result
     }
 
+    pub fn command_names() -> Vec<String> {
+        CommandRunner::default_commands().into_keys().collect()
+    }
+
     pub fn run(&mut self, command: &str) {
-        let mut arguments: Vec<String> = command.split_whitespace().map(String::from).collect();
+        let output = self.run_pipeline(command).unwrap_or_default();
+
+        // Nothing to print for commands like clear, which would leave an empty line.
+        if !output.is_empty() {
+            println!("{}", output);
+        }
+    }
+
+    // Commands separated by | run one after another, each getting the output of the one
+    // before it as input. Stops at the first one that fails.
+    pub fn run_pipeline(&mut self, command: &str) -> Result<String, ()> {
+        let mut input = None;
+
+        for stage in split_pipeline(command) {
+            let arguments: Vec<String> = stage.split_whitespace().map(String::from).collect();
+
+            if arguments.len() == 0 {
+                error("Input is empty.");
+                return Err(());
+            }
 
-        if arguments.len() == 0 {
-            error("Input is empty.");
-            return;
+            input = Some(self.run_command(arguments, input)?);
         }
 
-        println!("{}", self.run_command(arguments))
+        Ok(input.unwrap_or_default())
     }
 
-    pub fn run_command(&mut self, arguments: Vec<String>) -> String {
+    pub fn run_command(&mut self, arguments: Vec<String>, input: Option<String>) -> Result<String, ()> {
         let mut arguments: Vec<String> = arguments;
         let id = arguments[0].clone();
         arguments.remove(0);
@@ -50,13 +84,13 @@ impl CommandRunner {
 
         if let None = command_option {
             error("Command not found.");
-            return String::new();
+            return Err(());
         }
 
-        command_option.unwrap().run(Self::process_arguments(arguments))
+        command_option.unwrap().run(Self::process_arguments(arguments)?, input)
     }
 
-    fn process_arguments(arguments: Vec<String>) -> Vec<String> {
+    fn process_arguments(arguments: Vec<String>) -> Result<Vec<String>, ()> {
         let mut result: Vec<String> = Vec::new();
         let mut string: String = String::new();
 
@@ -77,7 +111,7 @@ impl CommandRunner {
 
                     if let None = variable_value {
                         error("Variable not found.");
-                        return Vec::new();
+                        return Err(());
                     }
 
                     result.push(variable_value.unwrap().to_string());
@@ -86,16 +120,21 @@ impl CommandRunner {
                 inline_command.push(String::from(argument.trim_end_matches(")")));
                 is_in_inline_command = false;
 
+                // Run like a line of its own, pipes and all.
                 let mut command_runner = CommandRunner::new();
-                result.push(command_runner.run_command(inline_command.clone()));
+                result.push(command_runner.run_pipeline(&inline_command.join(" "))?);
                 inline_command.clear();
             } else if is_in_inline_command {
                 inline_command.push(argument);
             } else if argument.starts_with("\\") {
                 if argument == "\\n" {
                     result.push("\n".to_string());
+                } else if argument.starts_with("\\e") {
+                    // Starts an escape sequence, like \e[31m for red text.
+                    result.push(argument.replacen("\\e", "\x1b", 1));
                 } else {
                     error("unknown escape sequence.");
+                    return Err(());
                 }
             } else if argument.starts_with("\"") && argument.ends_with("\"") {
                 result.push(argument.trim_start_matches("\"").trim_end_matches("\"").to_string());
@@ -119,10 +158,37 @@ impl CommandRunner {
             }
         }
 
-        result
+        Ok(result)
+    }
+}
+
+// Splits at every | outside of quotes and $().
+fn split_pipeline(command: &str) -> Vec<&str> {
+    let mut stages = Vec::new();
+    let mut start = 0;
+    let mut depth = 0;
+    let mut quoted = false;
+
+    for (index, character) in command.char_indices() {
+        match character {
+            '"' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted && 0 < depth => depth -= 1,
+            '|' if !quoted && depth == 0 => {
+                stages.push(&command[start..index]);
+                start = index + 1;
+            },
+            _ => { }
+        }
     }
+
+    stages.push(&command[start..]);
+
+    stages
 }
 
 pub trait Command {
-    fn run(&mut self, arguments: Vec<String>) -> String;
+    // The input is the output of the command before in a pipeline, None when there is none.
+    // Fails once it has reported an error, which stops the pipeline.
+    fn run(&mut self, arguments: Vec<String>, input: Option<String>) -> Result<String, ()>;
 }
\ No newline at end of file
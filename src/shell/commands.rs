@@ -1,19 +1,25 @@
+use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use core::arch::asm;
-use crate::{Color, ColorCode, error, OS_VERSION, WRITER};
-use crate::shell::command_runner::Command;
-use crate::shell::SHELL_ENVIRONMENT;
+use crate::{Color, ColorCode, console, error, framebuffer, OS_VERSION, text_mode, WRITER};
+use crate::encoding;
+use crate::vga_buffer::{self, CursorStyle};
+use crate::shell::command_runner::{Command, CommandRunner};
+use crate::shell::{SHELL_ENVIRONMENT, SHELL_HISTORY};
+use crate::task::{executor, keyboard, mouse, registry};
+use crate::thread;
+use crate::input;
 
 pub struct VersionCommand;
 
 impl Command for VersionCommand {
-    fn run(&mut self, arguments: Vec<String>) -> String {
+    fn run(&mut self, arguments: Vec<String>, _input: Option<String>) -> Result<String, ()> {
         if arguments.len() == 0 {
-            OS_VERSION.to_string()
+            Ok(OS_VERSION.to_string())
         } else {
             error("version expects 0 arguments.");
-            String::new()
+            Err(())
         }
     }
 }
@@ -21,84 +27,530 @@ impl Command for VersionCommand {
 pub struct EchoCommand;
 
 impl Command for EchoCommand {
-    fn run(&mut self, arguments: Vec<String>) -> String {
+    // Piped input comes after the arguments.
+    fn run(&mut self, arguments: Vec<String>, input: Option<String>) -> Result<String, ()> {
         let mut string = String::new();
         for argument in arguments {
             string.push_str(&argument);
             string.push(' ');
         }
 
-        string
+        if let Some(input) = input {
+            string.push_str(&input);
+        }
+
+        Ok(string)
     }
 }
 
 pub struct CalcCommand;
 
 impl Command for CalcCommand {
-    fn run(&mut self, arguments: Vec<String>) -> String {
+    // Piped input is read as more of the expression, after the arguments.
+    fn run(&mut self, arguments: Vec<String>, input: Option<String>) -> Result<String, ()> {
+        let mut arguments = arguments;
+        arguments.extend(input.iter().flat_map(|input| input.split_whitespace().map(String::from)));
+
         let calculator = crate::shell::calculator::Calculator::new(arguments);
-        calculator.calculate().to_string()
+        Ok(calculator.calculate().to_string())
     }
 }
 
 pub struct SetCommand;
 
 impl Command for SetCommand {
-    fn run(&mut self, arguments: Vec<String>) -> String {
+    fn run(&mut self, arguments: Vec<String>, _input: Option<String>) -> Result<String, ()> {
         if arguments.len() != 2 {
             error("set expects 2 arguments.");
-            return String::new();
+            return Err(());
         }
 
         SHELL_ENVIRONMENT.lock().variables.insert(arguments[0].to_string(), arguments[1].to_string());
-        arguments[1].to_string()
+        Ok(arguments[1].to_string())
     }
 }
 
+// Presets as (name, foreground, background).
+const THEMES: [(&str, Color, Color); 5] = [
+    ("default", Color::White, Color::Black),
+    ("dos", Color::LightGray, Color::Blue),
+    ("matrix", Color::LightGreen, Color::Black),
+    ("amber", Color::Yellow, Color::Black),
+    ("paper", Color::Black, Color::White)
+];
+
 pub struct ColorCommand;
 
 impl Command for ColorCommand {
-    fn run(&mut self, arguments: Vec<String>) -> String {
+    fn run(&mut self, arguments: Vec<String>, _input: Option<String>) -> Result<String, ()> {
+        let current = x86_64::instructions::interrupts::without_interrupts(|| WRITER.lock().default_color_code());
+        let current_background = Color::ALL[current.background() as usize & 0xF];
+
+        if arguments.len() == 0 {
+            let colors: Vec<&str> = Color::ALL.iter().map(|color| color.as_str()).collect();
+            let themes: Vec<&str> = THEMES.iter().map(|theme| theme.0).collect();
+
+            return Ok(format!("{} on {} (colors: {}; themes: {})",
+                              Color::ALL[current.foreground() as usize].as_str(),
+                              current_background.as_str(),
+                              colors.join(", "),
+                              themes.join(", ")));
+        } else if 2 < arguments.len() {
+            error("color expects 0 to 2 arguments.");
+            return Err(());
+        }
+
+        let theme = THEMES.iter().find(|theme| theme.0 == arguments[0]);
+
+        let (foreground, background) = match theme {
+            Some((_, foreground, background)) if arguments.len() == 1 => (*foreground, *background),
+            _ => {
+                // The background stays as it is unless given.
+                let foreground = Color::from_str(&arguments[0]);
+                let background = arguments.get(1).map_or(Some(current_background), |name| Color::from_str(name));
+
+                match (foreground, background) {
+                    (Some(foreground), Some(background)) => (foreground, background),
+                    _ => {
+                        error("invalid color.");
+                        return Err(());
+                    }
+                }
+            }
+        };
+
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            WRITER.lock().set_default_color_code(ColorCode::new(foreground, background));
+        });
+
+        Ok(String::new())
+    }
+}
+
+pub struct ClearCommand;
+
+impl Command for ClearCommand {
+    fn run(&mut self, arguments: Vec<String>, _input: Option<String>) -> Result<String, ()> {
+        if arguments.len() != 0 {
+            error("clear expects 0 arguments.");
+            return Err(());
+        }
+
+        // Blanks with the current background, so a new one fills the whole screen.
+        x86_64::instructions::interrupts::without_interrupts(|| WRITER.lock().clear());
+
+        Ok(String::new())
+    }
+}
+
+pub struct EditCommand;
+
+impl Command for EditCommand {
+    fn run(&mut self, arguments: Vec<String>, _input: Option<String>) -> Result<String, ()> {
+        if arguments.len() != 1 {
+            error("edit expects 1 argument.");
+            return Err(());
+        }
+
+        crate::shell::editor::edit(&arguments[0]);
+
+        Ok(String::new())
+    }
+}
+
+pub struct MoreCommand;
+
+impl Command for MoreCommand {
+    // Pages the piped input, or the output of the command given, which gets the input.
+    fn run(&mut self, arguments: Vec<String>, input: Option<String>) -> Result<String, ()> {
+        let output = match input {
+            Some(input) if arguments.len() == 0 => input,
+            _ if arguments.len() == 0 => {
+                error("more expects at least 1 argument or piped input.");
+                return Err(());
+            },
+            input => CommandRunner::new().run_command(arguments, input)?
+        };
+
+        crate::shell::pager::page(&output);
+
+        Ok(String::new())
+    }
+}
+
+pub struct TasksCommand;
+
+impl Command for TasksCommand {
+    fn run(&mut self, arguments: Vec<String>, _input: Option<String>) -> Result<String, ()> {
+        if arguments.len() != 0 {
+            error("tasks expects 0 arguments.");
+            return Err(());
+        }
+
+        let mut result = format!("{:<5} {:<10} {:<7} {:>8} {:>14}  {}", "ID", "STATE", "PRIO", "POLLS", "CYCLES", "NAME");
+
+        for task in registry::snapshot() {
+            result.push_str(&format!("\n{:<5} {:<10} {:<7} {:>8} {:>14}  {}",
+                                     task.id,
+                                     task.state.as_str(),
+                                     task.priority.as_str(),
+                                     task.polls,
+                                     task.poll_cycles,
+                                     task.name.as_deref().unwrap_or("-")));
+        }
+
+        result.push_str(&format!("\ndropped wakeups: {}, dropped scancodes: {}, dropped mouse bytes: {}, dropped input events: {}",
+                                 executor::dropped_wakeups(),
+                                 keyboard::dropped_scancodes(),
+                                 mouse::dropped_bytes(),
+                                 input::dropped_events()));
+
+        Ok(result)
+    }
+}
+
+pub struct ThreadsCommand;
+
+impl Command for ThreadsCommand {
+    fn run(&mut self, arguments: Vec<String>, _input: Option<String>) -> Result<String, ()> {
+        if arguments.len() != 0 {
+            error("threads expects 0 arguments.");
+            return Err(());
+        }
+
+        let mut result = format!("{:<5} {:<10} {}", "ID", "STATE", "NAME");
+
+        for thread in thread::snapshot() {
+            result.push_str(&format!("\n{:<5} {:<10} {}", thread.id, thread.state.as_str(), thread.name));
+        }
+
+        Ok(result)
+    }
+}
+
+pub struct KillCommand;
+
+impl Command for KillCommand {
+    fn run(&mut self, arguments: Vec<String>, _input: Option<String>) -> Result<String, ()> {
         if arguments.len() != 1 {
-            error("color expects 1 arguments.");
-            return String::new();
+            error("kill expects 1 argument.");
+            return Err(());
+        }
+
+        match arguments[0].parse::<u64>() {
+            Ok(id) => {
+                if !registry::abort(id) {
+                    error("No running task with this ID.");
+                    return Err(());
+                }
+            },
+            Err(_) => {
+                error("Task ID must be a number.");
+                return Err(());
+            }
+        }
+
+        Ok(String::new())
+    }
+}
+
+pub struct HistoryCommand;
+
+impl Command for HistoryCommand {
+    fn run(&mut self, arguments: Vec<String>, _input: Option<String>) -> Result<String, ()> {
+        if arguments.len() == 1 && arguments[0] == "clear" {
+            SHELL_HISTORY.lock().clear();
+            return Ok(String::new());
+        } else if arguments.len() != 0 {
+            error("history expects 0 arguments or clear.");
+            return Err(());
+        }
+
+        let history = SHELL_HISTORY.lock().history.clone();
+        let entries: Vec<String> = history.iter().enumerate()
+            .map(|(index, entry)| format!("{:>4}  {}", index + 1, entry))
+            .collect();
+
+        Ok(entries.join("\n"))
+    }
+}
+
+pub struct KeymapCommand;
+
+impl Command for KeymapCommand {
+    fn run(&mut self, arguments: Vec<String>, _input: Option<String>) -> Result<String, ()> {
+        if arguments.len() == 0 {
+            let layouts: Vec<&str> = keyboard::Layout::ALL.iter().map(|layout| layout.as_str()).collect();
+            return Ok(format!("{} (available: {})", keyboard::layout().as_str(), layouts.join(", ")));
+        } else if arguments.len() != 1 {
+            error("keymap expects 0 or 1 arguments.");
+            return Err(());
+        }
+
+        match keyboard::Layout::from_str(&arguments[0]) {
+            Some(layout) => keyboard::set_layout(layout),
+            None => {
+                error("Unknown keyboard layout.");
+                return Err(());
+            }
+        }
+
+        Ok(String::new())
+    }
+}
+
+pub struct CursorCommand;
+
+impl Command for CursorCommand {
+    fn run(&mut self, arguments: Vec<String>, _input: Option<String>) -> Result<String, ()> {
+        if arguments.len() == 0 {
+            let style = x86_64::instructions::interrupts::without_interrupts(|| WRITER.lock().cursor_style());
+
+            return Ok(String::from(match style {
+                CursorStyle::Block => "block",
+                CursorStyle::Underline => "underline"
+            }));
+        } else if arguments.len() != 1 {
+            error("cursor expects 0 or 1 arguments.");
+            return Err(());
+        }
+
+        let style = match &arguments[0] as &str {
+            "block" => CursorStyle::Block,
+            "underline" => CursorStyle::Underline,
+            _ => {
+                error("Unknown cursor style.");
+                return Err(());
+            }
+        };
+
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            WRITER.lock().set_cursor_style(style);
+        });
+
+        Ok(String::new())
+    }
+}
+
+pub struct FallbackCommand;
+
+impl Command for FallbackCommand {
+    fn run(&mut self, arguments: Vec<String>, _input: Option<String>) -> Result<String, ()> {
+        if arguments.len() == 0 {
+            return Ok(encoding::from_cp437(encoding::fallback_glyph()).to_string());
+        } else if arguments.len() != 1 {
+            error("fallback expects 0 or 1 arguments.");
+            return Err(());
+        }
+
+        let mut characters = arguments[0].chars();
+
+        match (characters.next(), characters.next()) {
+            (Some(character), None) => match encoding::to_cp437(character) {
+                Some(glyph) => encoding::set_fallback_glyph(glyph),
+                None => {
+                    error("The font has no glyph for this character.");
+                    return Err(());
+                }
+            },
+            _ => {
+                error("fallback expects a single character.");
+                return Err(());
+            }
+        }
+
+        Ok(String::new())
+    }
+}
+
+pub struct DmesgCommand;
+
+impl Command for DmesgCommand {
+    fn run(&mut self, arguments: Vec<String>, _input: Option<String>) -> Result<String, ()> {
+        if arguments.len() != 0 {
+            error("dmesg expects 0 arguments.");
+            return Err(());
+        }
+
+        let log = console::kernel_log();
+
+        Ok(log.trim_end().to_string())
+    }
+}
+
+pub struct ModeCommand;
+
+impl Command for ModeCommand {
+    fn run(&mut self, arguments: Vec<String>, _input: Option<String>) -> Result<String, ()> {
+        if framebuffer::text_console().is_some() {
+            error("mode only works in text mode.");
+            return Err(());
+        }
+
+        if arguments.len() == 0 {
+            let modes: Vec<&str> = text_mode::MODES.iter().map(|mode| mode.name).collect();
+            return Ok(format!("{} (available: {})", text_mode::current().name, modes.join(", ")));
+        } else if arguments.len() != 1 {
+            error("mode expects 0 or 1 arguments.");
+            return Err(());
         }
 
-        match &arguments[0] as &str  {
-            "red" => WRITER.lock().change_color_code(ColorCode::new(Color::Red, Color::Black)),
-            "green" => WRITER.lock().change_color_code(ColorCode::new(Color::Green, Color::Black)),
-            "blue" => WRITER.lock().change_color_code(ColorCode::new(Color::Blue, Color::Black)),
-            "yellow" => WRITER.lock().change_color_code(ColorCode::new(Color::Yellow, Color::Black)),
-            "cyan" => WRITER.lock().change_color_code(ColorCode::new(Color::Cyan, Color::Black)),
-            "magenta" => WRITER.lock().change_color_code(ColorCode::new(Color::Magenta, Color::Black)),
-            "white" => WRITER.lock().change_color_code(ColorCode::new(Color::White, Color::Black)),
-            "black" => WRITER.lock().change_color_code(ColorCode::new(Color::Black, Color::Black)),
-            _ => { error("invalid color.") }
+        match text_mode::find(&arguments[0]) {
+            Some(mode) => x86_64::instructions::interrupts::without_interrupts(|| {
+                text_mode::set(mode);
+                vga_buffer::set_size(mode.width, mode.height, mode.character_height);
+                console::resize();
+            }),
+            None => {
+                error("Unknown text mode.");
+                return Err(());
+            }
         }
 
-        String::new()
+        Ok(String::new())
+    }
+}
+
+pub struct DrawCommand;
+
+impl Command for DrawCommand {
+    // Shapes go straight onto the framebuffer, under the console text.
+    fn run(&mut self, arguments: Vec<String>, _input: Option<String>) -> Result<String, ()> {
+        let text_console = match framebuffer::text_console() {
+            Some(text_console) => text_console,
+            None => {
+                error("draw only works on the graphics console.");
+                return Err(());
+            }
+        };
+
+        if arguments.len() == 0 {
+            error("draw expects a shape: pixel, line, rect, fill or copy.");
+            return Err(());
+        }
+
+        let shape = &arguments[0] as &str;
+
+        let expected = match shape {
+            "pixel" => 3,
+            "line" | "rect" | "fill" => 5,
+            "copy" => 6,
+            _ => {
+                error("Unknown shape.");
+                return Err(());
+            }
+        };
+
+        // A pixel without a colour is read instead.
+        let reading = shape == "pixel" && arguments.len() == expected;
+
+        if arguments.len() != expected + 1 && !reading {
+            error(&format!("draw {} expects {} arguments.", shape, expected));
+            return Err(());
+        }
+
+        let colored = shape != "copy" && !reading;
+        let count = if colored { arguments.len() - 2 } else { arguments.len() - 1 };
+
+        // Kept small, so lines don't take ages to draw with interrupts disabled.
+        let numbers: Option<Vec<usize>> = arguments[1..=count].iter()
+            .map(|argument| argument.parse::<u16>().ok().map(usize::from))
+            .collect();
+
+        let numbers = match numbers {
+            Some(numbers) => numbers,
+            None => {
+                error("Coordinates and sizes have to be numbers up to 65535.");
+                return Err(());
+            }
+        };
+
+        let drawn = x86_64::instructions::interrupts::without_interrupts(|| {
+            let mut text_console = text_console.lock();
+            let framebuffer = text_console.framebuffer();
+
+            // Colours by name, or any other pixel value by number.
+            let value = if colored {
+                let name = &arguments[arguments.len() - 1];
+
+                match Color::from_str(name) {
+                    Some(color) => framebuffer.color(color as u8),
+                    None => name.parse().map_err(|_| "invalid color.")?
+                }
+            } else {
+                0
+            };
+
+            match shape {
+                "pixel" if reading => {
+                    return framebuffer.pixel(numbers[0], numbers[1])
+                        .map(|value| value.to_string())
+                        .ok_or("The pixel is off screen.");
+                },
+                "pixel" => framebuffer.set_pixel(numbers[0], numbers[1], value),
+                "line" => framebuffer.line(numbers[0] as isize, numbers[1] as isize, numbers[2] as isize, numbers[3] as isize, value),
+                "rect" => framebuffer.rect(numbers[0], numbers[1], numbers[2], numbers[3], value),
+                "fill" => framebuffer.fill_rect(numbers[0], numbers[1], numbers[2], numbers[3], value),
+                _ => {
+                    let (x, y, width, height) = (numbers[0], numbers[1], numbers[2], numbers[3]);
+
+                    if framebuffer.width() < width || framebuffer.height() < height {
+                        return Err("The region is larger than the screen.");
+                    }
+
+                    let pixels: Vec<u32> = (0..width * height)
+                        .map(|index| framebuffer.pixel(x.saturating_add(index % width), y.saturating_add(index / width)).unwrap_or(0))
+                        .collect();
+
+                    framebuffer.blit(numbers[4], numbers[5], width, &pixels);
+                }
+            }
+
+            Ok(String::new())
+        });
+
+        // Reported once the console is unlocked, printing draws on it too.
+        drawn.map_err(|message| error(message))
     }
 }
 
 pub struct HelpCommand;
 
 impl Command for HelpCommand {
-    fn run(&mut self, arguments: Vec<String>) -> String {
+    fn run(&mut self, arguments: Vec<String>, _input: Option<String>) -> Result<String, ()> {
         if arguments.len() == 0 {
-            return String::from("available commands: version, echo, calc, set, color, help");
+            return Ok(String::from("available commands: version, echo, calc, set, color, tasks, threads, kill, history, keymap, cursor, fallback, dmesg, mode, draw, clear, edit, more, help"));
         } else if arguments.len() != 1 {
             error("help expects 1 arguments.");
-            return String::new();
+            return Err(());
         }
 
-        return match &arguments[0] as &str {
+        let help = match &arguments[0] as &str {
             "version" => "version - (0 arguments) prints the version of the operating system.",
-            "echo" => "echo - (many arguments; any type) prints the arguments.",
-            "calc" => "calc - (many arguments; + - * / numbers) calculates the arguments.",
+            "echo" => "echo - (many arguments; any type) prints the arguments, followed by piped input.",
+            "calc" => "calc - (many arguments; + - * / numbers) calculates the arguments, followed by piped input.",
             "set" => "set - (2 arguments; key value) sets a variable.",
-            "color" => "color - (1 argument; red green blue yellow cyan magenta white black) changes the color of the text.",
+            "color" => "color - (0 to 2 arguments; foreground [background] or a theme) shows or changes the colors, any of the 16 for either.",
+            "clear" => "clear - (0 arguments) clears the screen, with the current background.",
+            "edit" => "edit - (1 argument; variable) edits a variable full-screen, ^O saves it and ^X leaves.",
+            "more" => "more - (many arguments; command, or piped input) shows the output of the command or the input a screen at a time, space and b page, / searches, q quits.",
+            "tasks" => "tasks - (0 arguments) lists tasks run by the executor.",
+            "threads" => "threads - (0 arguments) lists kernel threads.",
+            "kill" => "kill - (1 argument; task id) cancels a task.",
+            "history" => "history - (0 arguments or clear) lists previous commands, run one again with !n or the last one with !!.",
+            "keymap" => "keymap - (0 or 1 argument; us uk de azerty dvorak pl) shows or changes the keyboard layout.",
+            "cursor" => "cursor - (0 or 1 argument; block underline) shows or changes the shape of the cursor.",
+            "fallback" => "fallback - (0 or 1 argument; character) shows or changes what is shown for characters the font lacks.",
+            "dmesg" => "dmesg - (0 arguments) prints the kernel messages, from boot on.",
+            "mode" => "mode - (0 or 1 argument; 80x25 80x50 90x30 90x60) shows or changes the size of the text screen.",
+            "draw" => "draw - (pixel x y [color], line x0 y0 x1 y1 color, rect or fill x y width height color, copy x y width height x y) draws on the graphics console.",
             "help" => "help - (1 argument; command) prints the help for the command.",
-            _ => { error("invalid command."); "" }
-        }.to_string();
+            _ => {
+                error("invalid command.");
+                return Err(());
+            }
+        };
+
+        Ok(help.to_string())
     }
 }
\ No newline at end of file
@@ -0,0 +1,61 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::shell::SHELL_ENVIRONMENT;
+use crate::shell::command_runner::CommandRunner;
+
+pub struct Completion {
+    // Where the completed word starts in the line.
+    pub start: usize,
+    // What the word should be replaced with, the longest prefix all candidates share.
+    pub replacement: String,
+    pub candidates: Vec<String>
+}
+
+// Completes the word that ends at the cursor. The first word of the line, or after a
+// `|`, is a command name, words starting with `$` are variable names. Paths will follow once there is a
+// filesystem.
+pub fn complete(line: &[char], cursor: usize) -> Option<Completion> {
+    let mut start = cursor;
+
+    while 0 < start && !line[start - 1].is_whitespace() {
+        start -= 1;
+    }
+
+    let word: String = line[start..cursor].iter().collect();
+    let command_start = line[..start].iter().rposition(|character| *character == '|').map_or(0, |index| index + 1);
+    let is_first_word = line[command_start..start].iter().all(|character| character.is_whitespace());
+
+    let names: Vec<String> = if word.starts_with("$") && !word.starts_with("$(") {
+        SHELL_ENVIRONMENT.lock().variables.keys().map(|name| format!("${}", name)).collect()
+    } else if is_first_word {
+        CommandRunner::command_names()
+    } else {
+        return None;
+    };
+
+    let candidates: Vec<String> = names.into_iter().filter(|name| name.starts_with(&word)).collect();
+
+    if candidates.is_empty() {
+        return None;
+    }
+
+    let mut replacement = common_prefix(&candidates);
+
+    if candidates.len() == 1 {
+        replacement.push(' ');
+    }
+
+    Some(Completion { start, replacement, candidates })
+}
+
+fn common_prefix(candidates: &[String]) -> String {
+    let mut prefix: Vec<char> = candidates[0].chars().collect();
+
+    for candidate in &candidates[1..] {
+        let shared = prefix.iter().zip(candidate.chars()).take_while(|(a, b)| **a == *b).count();
+        prefix.truncate(shared);
+    }
+
+    prefix.into_iter().collect()
+}
\ No newline at end of file
@@ -0,0 +1,300 @@
+use alloc::format;
+use alloc::string::String;
+use crate::input::{Key, KeyEvent};
+use crate::screen::{Canvas, FullScreen};
+use crate::shell::SHELL_ENVIRONMENT;
+use crate::shell::gap_buffer::GapBuffer;
+
+// Spaces a tab inserts.
+const TAB_WIDTH: usize = 4;
+
+const SHORTCUTS: &str = "^O Save  ^X Exit  ^W Search  ^K Cut line  ^U Paste";
+
+// Edits a shell variable full-screen, in the manner of nano. There is no filesystem
+// yet, so saving stores the text back into the variable.
+pub fn edit(name: &str) {
+    let text = SHELL_ENVIRONMENT.lock().variables.get(name).cloned().unwrap_or_default();
+
+    let mut screen = FullScreen::enter();
+    let mut editor = Editor::new(name, &text);
+
+    loop {
+        editor.draw(screen.canvas());
+
+        let event = screen.read_key();
+
+        if !editor.handle(&mut screen, event) {
+            break;
+        }
+    }
+}
+
+struct Editor {
+    name: String,
+    buffer: GapBuffer,
+    // The first line and column shown.
+    top: usize,
+    left: usize,
+    // Where up and down try to keep the cursor, even across shorter lines.
+    goal_column: Option<usize>,
+    modified: bool,
+    cut: String,
+    // Set right after cutting a line, so the next cut adds to it.
+    cutting: bool,
+    query: String,
+    message: String,
+    // Set after Ctrl-X with unsaved changes, a second one leaves anyway.
+    confirm_exit: bool
+}
+
+impl Editor {
+    fn new(name: &str, text: &str) -> Editor {
+        let mut buffer = GapBuffer::new(text);
+        buffer.move_to(0);
+
+        Editor {
+            name: String::from(name),
+            buffer,
+            top: 0,
+            left: 0,
+            goal_column: None,
+            modified: false,
+            cut: String::new(),
+            cutting: false,
+            query: String::new(),
+            message: String::new(),
+            confirm_exit: false
+        }
+    }
+
+    // Returns false once the editor should close.
+    fn handle(&mut self, screen: &mut FullScreen, event: KeyEvent) -> bool {
+        let modifiers = event.modifiers;
+        let page = text_rows(screen.canvas());
+
+        let confirm_exit = core::mem::take(&mut self.confirm_exit);
+        let cutting = core::mem::take(&mut self.cutting);
+        self.message.clear();
+
+        match event.key {
+            Key::Char(character) if modifiers.ctrl => match character.to_ascii_lowercase() {
+                'x' if self.modified && !confirm_exit => {
+                    self.message = String::from("Unsaved changes, ^X again to leave without saving or ^O to save.");
+                    self.confirm_exit = true;
+                },
+                'x' => return false,
+                'o' => self.save(),
+                'w' => self.search(screen),
+                'k' => self.cut_line(cutting),
+                'u' => self.paste(),
+                _ => { }
+            },
+            Key::Char(character) if !modifiers.alt => self.insert(character),
+            Key::Enter => self.insert('\n'),
+            Key::Tab => {
+                for _ in 0..TAB_WIDTH {
+                    self.insert(' ');
+                }
+            },
+            Key::Backspace => {
+                if self.buffer.delete_before().is_some() {
+                    self.changed();
+                }
+            },
+            Key::Delete => {
+                if self.buffer.delete_after().is_some() {
+                    self.changed();
+                }
+            },
+            Key::ArrowLeft => self.move_to(self.buffer.cursor().saturating_sub(1)),
+            Key::ArrowRight => self.move_to(self.buffer.cursor() + 1),
+            Key::ArrowUp => self.move_vertically(-1),
+            Key::ArrowDown => self.move_vertically(1),
+            Key::PageUp => self.move_vertically(-(page as isize)),
+            Key::PageDown => self.move_vertically(page as isize),
+            Key::Home => self.move_to(self.line_start(self.buffer.cursor())),
+            Key::End => self.move_to(self.line_end(self.buffer.cursor())),
+            _ => { }
+        }
+
+        true
+    }
+
+    fn insert(&mut self, character: char) {
+        self.buffer.insert(character);
+        self.changed();
+    }
+
+    fn changed(&mut self) {
+        self.modified = true;
+        self.goal_column = None;
+    }
+
+    fn move_to(&mut self, position: usize) {
+        self.buffer.move_to(position);
+        self.goal_column = None;
+    }
+
+    // By whole lines, up for negative ones. Stops at the first and last line.
+    fn move_vertically(&mut self, lines: isize) {
+        let cursor = self.buffer.cursor();
+        let column = *self.goal_column.get_or_insert(cursor - self.line_start(cursor));
+        let mut start = self.line_start(cursor);
+
+        for _ in 0..lines.unsigned_abs() {
+            if lines < 0 {
+                if start == 0 {
+                    break;
+                }
+
+                start = self.line_start(start - 1);
+            } else {
+                let end = self.line_end(start);
+
+                if end == self.buffer.len() {
+                    break;
+                }
+
+                start = end + 1;
+            }
+        }
+
+        let end = self.line_end(start);
+        self.buffer.move_to(start + column.min(end - start));
+    }
+
+    fn line_start(&self, position: usize) -> usize {
+        (0..position).rev().find(|index| self.buffer.get(*index) == Some('\n')).map_or(0, |index| index + 1)
+    }
+
+    // Where the newline ending the line is, or the end of the text.
+    fn line_end(&self, position: usize) -> usize {
+        (position..self.buffer.len()).find(|index| self.buffer.get(*index) == Some('\n')).unwrap_or(self.buffer.len())
+    }
+
+    fn line_of(&self, position: usize) -> usize {
+        (0..position).filter(|index| self.buffer.get(*index) == Some('\n')).count()
+    }
+
+    fn save(&mut self) {
+        let text = self.buffer.text();
+        let lines = text.lines().count();
+
+        SHELL_ENVIRONMENT.lock().variables.insert(self.name.clone(), text);
+
+        self.modified = false;
+        self.message = format!("Saved {} lines to ${}.", lines, self.name);
+    }
+
+    // Finds the next match after the cursor, going around to the top at the end.
+    fn search(&mut self, screen: &mut FullScreen) {
+        let row = screen.canvas().height() - 2;
+
+        let query = match screen.read_line(row, "Search: ", &self.query) {
+            Some(query) if !query.is_empty() => query,
+            _ => return
+        };
+
+        let cursor = self.buffer.cursor();
+        let found = self.buffer.find(&query, cursor + 1).or_else(|| self.buffer.find(&query, 0));
+
+        match found {
+            Some(position) => self.move_to(position),
+            None => self.message = format!("\"{}\" not found.", query)
+        }
+
+        self.query = query;
+    }
+
+    // Cuts the line the cursor is on, newline included. Cutting again right away adds
+    // the next line to what was cut.
+    fn cut_line(&mut self, cutting: bool) {
+        let start = self.line_start(self.buffer.cursor());
+        let end = (self.line_end(start) + 1).min(self.buffer.len());
+
+        if !cutting {
+            self.cut.clear();
+        }
+
+        self.buffer.move_to(start);
+
+        for _ in start..end {
+            if let Some(character) = self.buffer.delete_after() {
+                self.cut.push(character);
+            }
+        }
+
+        self.cutting = true;
+        self.changed();
+    }
+
+    fn paste(&mut self) {
+        if self.cut.is_empty() {
+            return;
+        }
+
+        self.buffer.insert_str(&self.cut);
+        self.changed();
+    }
+
+    fn draw(&mut self, canvas: Canvas) {
+        let color_code = canvas.default_color_code();
+        let bar = color_code.inverted();
+        let (width, height) = (canvas.width(), canvas.height());
+        let rows = text_rows(canvas);
+
+        // Scrolls so the cursor stays on screen.
+        let cursor = self.buffer.cursor();
+        let line = self.line_of(cursor);
+        let column = cursor - self.line_start(cursor);
+
+        if line < self.top {
+            self.top = line;
+        } else if self.top + rows <= line {
+            self.top = line + 1 - rows;
+        }
+
+        if column < self.left {
+            self.left = column;
+        } else if self.left + width <= column {
+            self.left = column + 1 - width;
+        }
+
+        let modified = if self.modified { "  [Modified]" } else { "" };
+        canvas.fill_row(0, &format!("  edit  ${}{}", self.name, modified), bar);
+
+        let mut start = (0..self.top).fold(0, |start, _| (self.line_end(start) + 1).min(self.buffer.len()));
+        let mut past_end = self.top > self.line_of(self.buffer.len());
+
+        for row in 0..rows {
+            let text = if past_end {
+                String::new()
+            } else {
+                let end = self.line_end(start);
+                let text = self.buffer.slice((start + self.left).min(end), end);
+
+                past_end = end == self.buffer.len();
+                start = end + 1;
+
+                text
+            };
+
+            canvas.fill_row(row + 1, &text, color_code);
+
+            // Marks lines that go on past the right edge.
+            if width < text.chars().count() {
+                canvas.put(row + 1, width - 1, '$', bar);
+            }
+        }
+
+        canvas.fill_row(height - 2, &self.message, color_code);
+        canvas.fill_row(height - 1, SHORTCUTS, bar);
+
+        canvas.set_cursor(Some((line - self.top + 1, column - self.left)));
+    }
+}
+
+// Between the title and the two rows at the bottom.
+fn text_rows(canvas: Canvas) -> usize {
+    canvas.height().saturating_sub(3).max(1)
+}
\ No newline at end of file
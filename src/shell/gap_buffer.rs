@@ -0,0 +1,121 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Room added to the gap whenever it runs out.
+const GAP_SIZE: usize = 64;
+
+// Text kept as characters with a gap at the cursor, so typing and deleting there only
+// touch the gap. Moving the cursor moves the gap along.
+pub struct GapBuffer {
+    characters: Vec<char>,
+    gap_start: usize,
+    gap_end: usize
+}
+
+impl GapBuffer {
+    pub fn new(text: &str) -> GapBuffer {
+        let mut characters: Vec<char> = text.chars().collect();
+        let length = characters.len();
+        characters.extend(vec![' '; GAP_SIZE]);
+
+        GapBuffer {
+            characters,
+            gap_start: length,
+            gap_end: length + GAP_SIZE
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.characters.len() - (self.gap_end - self.gap_start)
+    }
+
+    // Where the next character gets inserted.
+    pub fn cursor(&self) -> usize {
+        self.gap_start
+    }
+
+    pub fn get(&self, index: usize) -> Option<char> {
+        if index < self.gap_start {
+            Some(self.characters[index])
+        } else {
+            self.characters.get(index + self.gap_end - self.gap_start).copied()
+        }
+    }
+
+    pub fn move_to(&mut self, position: usize) {
+        let position = position.min(self.len());
+
+        while position < self.gap_start {
+            self.gap_start -= 1;
+            self.gap_end -= 1;
+            self.characters[self.gap_end] = self.characters[self.gap_start];
+        }
+
+        while self.gap_start < position {
+            self.characters[self.gap_start] = self.characters[self.gap_end];
+            self.gap_start += 1;
+            self.gap_end += 1;
+        }
+    }
+
+    pub fn insert(&mut self, character: char) {
+        if self.gap_start == self.gap_end {
+            let tail = self.characters.len() - self.gap_end;
+            self.characters.splice(self.gap_end..self.gap_end, vec![' '; GAP_SIZE]);
+            self.gap_end = self.characters.len() - tail;
+        }
+
+        self.characters[self.gap_start] = character;
+        self.gap_start += 1;
+    }
+
+    pub fn insert_str(&mut self, text: &str) {
+        for character in text.chars() {
+            self.insert(character);
+        }
+    }
+
+    // Backspace, returns what was deleted.
+    pub fn delete_before(&mut self) -> Option<char> {
+        if self.gap_start == 0 {
+            return None;
+        }
+
+        self.gap_start -= 1;
+
+        Some(self.characters[self.gap_start])
+    }
+
+    // Delete, returns what was deleted.
+    pub fn delete_after(&mut self) -> Option<char> {
+        if self.gap_end == self.characters.len() {
+            return None;
+        }
+
+        self.gap_end += 1;
+
+        Some(self.characters[self.gap_end - 1])
+    }
+
+    pub fn text(&self) -> String {
+        self.slice(0, self.len())
+    }
+
+    // The characters from start up to end.
+    pub fn slice(&self, start: usize, end: usize) -> String {
+        (start..end.min(self.len())).filter_map(|index| self.get(index)).collect()
+    }
+
+    pub fn find(&self, query: &str, from: usize) -> Option<usize> {
+        let query: Vec<char> = query.chars().collect();
+
+        if query.is_empty() {
+            return None;
+        }
+
+        (from..=self.len().saturating_sub(query.len())).find(|start| {
+            query.iter().enumerate().all(|(offset, character)| self.get(start + offset) == Some(*character))
+        })
+    }
+}
\ No newline at end of file
@@ -0,0 +1,310 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+use crate::encoding;
+use crate::vga_buffer::{self, Writer};
+
+// Keeps the line being typed in memory and redraws it from there after every edit.
+// The line starts where the writer was when the editor was created (right after the
+// prompt) and wraps over as many rows as it needs.
+pub struct LineEditor {
+    // The console the line is typed on, whichever thread is printing elsewhere.
+    writer: &'static Mutex<Writer>,
+    buffer: Vec<char>,
+    cursor: usize,
+    killed: Vec<char>,
+    origin_row: usize,
+    origin_column: usize,
+    // Drawn in front of the line without being part of it, like the reverse search prompt.
+    label: Vec<char>,
+    // Characters drawn by the last render, so a shorter line can blank out the rest.
+    rendered: usize
+}
+
+impl LineEditor {
+    pub fn new(writer: &'static Mutex<Writer>) -> LineEditor {
+        let (origin_row, origin_column) = without_interrupts(|| {
+            let writer = writer.lock();
+            (writer.row_position, writer.column_position)
+        });
+
+        LineEditor {
+            writer,
+            buffer: Vec::new(),
+            cursor: 0,
+            killed: Vec::new(),
+            origin_row,
+            origin_column,
+            label: Vec::new(),
+            rendered: 0
+        }
+    }
+
+    pub fn line(&self) -> String {
+        self.buffer.iter().collect()
+    }
+
+    pub fn contents(&self) -> &[char] {
+        &self.buffer
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    // Replaces everything from start up to the cursor with text.
+    pub fn replace(&mut self, start: usize, text: &str) {
+        let room = self.capacity() - (self.buffer.len() - (self.cursor - start));
+        let text: Vec<char> = text.chars().take(room).collect();
+        let length = text.len();
+
+        self.buffer.splice(start..self.cursor, text);
+        self.cursor = start + length;
+
+        self.render();
+    }
+
+    // Starts the line over at the writer's position, after something was printed in
+    // the middle of editing it.
+    pub fn redraw(&mut self) {
+        let (origin_row, origin_column) = without_interrupts(|| {
+            let writer = self.writer.lock();
+            (writer.row_position, writer.column_position)
+        });
+
+        self.origin_row = origin_row;
+        self.origin_column = origin_column;
+        self.rendered = 0;
+
+        self.buffer.truncate(self.capacity());
+        self.cursor = self.cursor.min(self.buffer.len());
+
+        self.render();
+    }
+
+    pub fn set_label(&mut self, label: &str) {
+        self.label = label.chars().collect();
+        self.buffer.truncate(self.capacity());
+        self.cursor = self.cursor.min(self.buffer.len());
+
+        self.render();
+    }
+
+    pub fn set_line(&mut self, line: &str) {
+        self.buffer = line.chars().collect();
+        self.buffer.truncate(self.capacity());
+        self.cursor = self.buffer.len();
+
+        self.render();
+    }
+
+    pub fn insert(&mut self, character: char) {
+        if self.capacity() <= self.buffer.len() {
+            return;
+        }
+
+        self.buffer.insert(self.cursor, character);
+        self.cursor += 1;
+
+        self.render();
+    }
+
+    pub fn backspace(&mut self) {
+        if self.cursor == 0 {
+            return;
+        }
+
+        self.cursor -= 1;
+        self.buffer.remove(self.cursor);
+
+        self.render();
+    }
+
+    pub fn delete(&mut self) {
+        if self.buffer.len() <= self.cursor {
+            return;
+        }
+
+        self.buffer.remove(self.cursor);
+
+        self.render();
+    }
+
+    pub fn move_left(&mut self) {
+        if 0 < self.cursor {
+            self.move_to(self.cursor - 1);
+        }
+    }
+
+    pub fn move_right(&mut self) {
+        if self.cursor < self.buffer.len() {
+            self.move_to(self.cursor + 1);
+        }
+    }
+
+    pub fn move_home(&mut self) {
+        self.move_to(0);
+    }
+
+    pub fn move_end(&mut self) {
+        self.move_to(self.buffer.len());
+    }
+
+    pub fn move_word_left(&mut self) {
+        self.move_to(self.previous_word_start());
+    }
+
+    pub fn move_word_right(&mut self) {
+        self.move_to(self.next_word_end());
+    }
+
+    pub fn kill_to_end(&mut self) {
+        let cursor = self.cursor;
+        self.kill(cursor, self.buffer.len());
+    }
+
+    pub fn kill_to_start(&mut self) {
+        let cursor = self.cursor;
+        self.kill(0, cursor);
+    }
+
+    pub fn kill_previous_word(&mut self) {
+        let start = self.previous_word_start();
+        self.kill(start, self.cursor);
+    }
+
+    pub fn kill_next_word(&mut self) {
+        let end = self.next_word_end();
+        self.kill(self.cursor, end);
+    }
+
+    pub fn insert_str(&mut self, text: &str) {
+        for character in text.chars() {
+            if self.capacity() <= self.buffer.len() {
+                break;
+            }
+
+            self.buffer.insert(self.cursor, character);
+            self.cursor += 1;
+        }
+
+        self.render();
+    }
+
+    // Inserts whatever was killed last.
+    pub fn yank(&mut self) {
+        let killed: String = self.killed.iter().collect();
+        self.insert_str(&killed);
+    }
+
+    // Leaves the writer right after the line, so output continues below it.
+    pub fn finish(&mut self) -> String {
+        let end = self.start() + self.buffer.len();
+
+        without_interrupts(|| {
+            if end == 0 {
+                self.writer.lock().move_to(self.origin_row, 0);
+            } else {
+                self.writer.lock().move_to(self.origin_row + (end - 1) / vga_buffer::width(), (end - 1) % vga_buffer::width() + 1);
+            }
+        });
+
+        self.line()
+    }
+
+    fn kill(&mut self, start: usize, end: usize) {
+        if start == end {
+            return;
+        }
+
+        self.killed = self.buffer.drain(start..end).collect();
+        self.cursor = start;
+
+        self.render();
+    }
+
+    fn move_to(&mut self, cursor: usize) {
+        self.cursor = cursor;
+
+        let position = self.start() + cursor;
+        let row = self.origin_row + position / vga_buffer::width();
+
+        without_interrupts(|| {
+            self.writer.lock().move_to(row, position % vga_buffer::width());
+        });
+    }
+
+    fn previous_word_start(&self) -> usize {
+        let mut position = self.cursor;
+
+        while 0 < position && self.buffer[position - 1].is_whitespace() {
+            position -= 1;
+        }
+
+        while 0 < position && !self.buffer[position - 1].is_whitespace() {
+            position -= 1;
+        }
+
+        position
+    }
+
+    fn next_word_end(&self) -> usize {
+        let mut position = self.cursor;
+
+        while position < self.buffer.len() && self.buffer[position].is_whitespace() {
+            position += 1;
+        }
+
+        while position < self.buffer.len() && !self.buffer[position].is_whitespace() {
+            position += 1;
+        }
+
+        position
+    }
+
+    // Where the line itself starts, behind the label.
+    fn start(&self) -> usize {
+        self.origin_column + self.label.len()
+    }
+
+    // The line may fill the whole screen, but no more.
+    fn capacity(&self) -> usize {
+        (vga_buffer::width() * vga_buffer::height() - 1).saturating_sub(self.start())
+    }
+
+    fn render(&mut self) {
+        let length = self.label.len() + self.buffer.len();
+
+        without_interrupts(|| {
+            let mut writer = self.writer.lock();
+
+            // Scroll if the line, with the cursor after it, runs past the last row.
+            let last_row = self.origin_row + (self.origin_column + length) / vga_buffer::width();
+
+            if vga_buffer::height() <= last_row {
+                for _ in 0..=(last_row - vga_buffer::height()) {
+                    writer.scroll_up();
+                    self.origin_row -= 1;
+                }
+            }
+
+            let mut characters = self.label.iter().chain(self.buffer.iter());
+
+            for index in 0..length.max(self.rendered) {
+                let position = self.origin_column + index;
+                let byte = match characters.next() {
+                    Some(character) => encoding::encode(*character),
+                    None => 0x0
+                };
+
+                writer.write_at(self.origin_row + position / vga_buffer::width(), position % vga_buffer::width(), byte);
+            }
+
+            let position = self.start() + self.cursor;
+            writer.move_to(self.origin_row + position / vga_buffer::width(), position % vga_buffer::width());
+        });
+
+        self.rendered = length;
+    }
+}
\ No newline at end of file
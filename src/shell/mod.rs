@@ -1,16 +1,25 @@
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, VecDeque};
+use core::sync::atomic::{AtomicBool, Ordering};
+use conquer_once::spin::OnceCell;
 use lazy_static::lazy_static;
-use crate::{Color, ColorCode, OS_VERSION, print, println, WRITER};
-use crate::reading::READER;
+use crate::{Color, console, error, OS_VERSION, print, println, WRITER};
 use crate::shell::command_runner::CommandRunner;
 use spin::Mutex;
 use alloc::vec::Vec;
 use alloc::string::String;
+use crate::thread::{self, ThreadId};
 
 mod command_runner;
 mod commands;
 mod calculator;
-
+mod completion;
+mod editor;
+mod gap_buffer;
+mod pager;
+mod line_editor;
+pub mod prompt;
+
+// History and variables are shared by the shells on all consoles.
 lazy_static! {
     pub static ref SHELL_HISTORY: Mutex<ShellHistory> = Mutex::new(ShellHistory::new());
 }
@@ -19,6 +28,77 @@ lazy_static! {
     pub static ref SHELL_ENVIRONMENT: Mutex<ShellEnvironment> = Mutex::new(ShellEnvironment::new());
 }
 
+lazy_static! {
+    static ref SHELLS: Vec<Shell> = (0..console::CONSOLE_COUNT).map(Shell::new).collect();
+}
+
+// The shell running on one of the consoles.
+pub struct Shell {
+    console: usize,
+    input: Mutex<VecDeque<String>>,
+    thread: OnceCell<ThreadId>,
+    awaits_input: AtomicBool
+}
+
+impl Shell {
+    fn new(console: usize) -> Shell {
+        Shell {
+            console,
+            input: Mutex::new(VecDeque::new()),
+            thread: OnceCell::uninit(),
+            awaits_input: AtomicBool::new(false)
+        }
+    }
+
+    pub fn console(&self) -> usize {
+        self.console
+    }
+
+    // Whether the prompt is shown and no command is running.
+    pub fn awaits_input(&self) -> bool {
+        self.awaits_input.load(Ordering::Relaxed)
+    }
+
+    pub fn run(&self, input: String) {
+        if !self.awaits_input.swap(false, Ordering::Relaxed) {
+            return;
+        }
+
+        self.input.lock().push_back(input);
+        thread::unpark(*self.thread.try_get().expect("Shell not started."));
+    }
+
+    // Commands run on the shell's own thread, so a slow one doesn't stall the executor
+    // and with it keyboard handling. Everything it prints goes to its console.
+    fn start(&'static self) {
+        let id = thread::spawn("shell", move || {
+            console::attach(self.console);
+
+            print_info_message();
+            self.prompt();
+
+            loop {
+                let input = self.input.lock().pop_front();
+
+                match input {
+                    Some(input) => {
+                        execute(&input);
+                        self.prompt();
+                    },
+                    None => thread::park()
+                }
+            }
+        });
+
+        self.thread.try_init_once(|| id).expect("Shell should be started only once.");
+    }
+
+    fn prompt(&self) {
+        print!("> ");
+        self.awaits_input.store(true, Ordering::Relaxed);
+    }
+}
+
 pub struct ShellEnvironment {
     pub variables: BTreeMap<String, String>
 }
@@ -31,6 +111,10 @@ impl ShellEnvironment {
     }
 }
 
+// Entries kept before the oldest ones get dropped.
+pub const HISTORY_SIZE: usize = 100;
+
+// Only lives in memory for now, there is no filesystem to save it to yet.
 pub struct ShellHistory {
     pub history: Vec<String>,
     pub index: u64
@@ -43,48 +127,123 @@ impl ShellHistory {
             index: 0
         }
     }
-}
 
-pub fn initial_run() {
-    print!("> ");
-    READER.lock().awaits_input = true;
-    READER.lock().column_position_start = 0;
-}
+    // Skips empty lines and repeats of the last entry.
+    pub fn push(&mut self, entry: String) {
+        if !entry.trim().is_empty() && self.history.last() != Some(&entry) {
+            self.history.push(entry);
 
-pub fn run() {
-    if READER.lock().awaits_input {
-        return;
+            if HISTORY_SIZE < self.history.len() {
+                self.history.remove(0);
+            }
+        }
+
+        self.index = self.history.len() as u64;
     }
 
-    let row = WRITER.lock().row_position;
-    let mut input = WRITER.lock().row_into_string(row - 1);
+    pub fn clear(&mut self) {
+        self.history.clear();
+        self.index = 0;
+    }
 
-    // Remove input indicator.
-    input.remove(0);
+    pub fn previous(&mut self) -> Option<String> {
+        if self.index == 0 {
+            return None;
+        }
 
-    SHELL_HISTORY.lock().history.push(input.clone());
-    let length = SHELL_HISTORY.lock().history.len() as u64;
-    SHELL_HISTORY.lock().index = length;
+        self.index -= 1;
 
-    let mut command_runner = CommandRunner::new();
+        self.history.get(self.index as usize).cloned()
+    }
 
-    command_runner.run(&input);
+    // Stepping past the newest entry gives back an empty line.
+    pub fn next(&mut self) -> Option<String> {
+        if self.history.len() as u64 <= self.index {
+            return None;
+        }
 
-    READER.lock().column_position_start = 0;
+        self.index += 1;
 
-    print!("> ");
-    READER.lock().awaits_input = true;
-    READER.lock().column_position_start = 0;
+        Some(self.history.get(self.index as usize).cloned().unwrap_or_default())
+    }
+
+    // Index of the newest entry before `before` that contains the query.
+    pub fn search(&self, query: &str, before: usize) -> Option<usize> {
+        (0..before.min(self.history.len())).rev().find(|index| self.history[*index].contains(query))
+    }
+
+    // Replaces `!!` with the last entry and `!n` with entry number n, as listed by
+    // the history command. Returns None if an entry doesn't exist.
+    pub fn expand(&self, input: &str) -> Option<String> {
+        let mut result = String::new();
+        let mut characters = input.chars().peekable();
+
+        while let Some(character) = characters.next() {
+            if character != '!' {
+                result.push(character);
+                continue;
+            }
+
+            match characters.peek() {
+                Some('!') => {
+                    characters.next();
+                    result.push_str(self.history.last()?);
+                },
+                Some(digit) if digit.is_ascii_digit() => {
+                    let mut number = 0;
+
+                    while let Some(digit) = characters.peek().and_then(|digit| digit.to_digit(10)) {
+                        number = number * 10 + digit as usize;
+                        characters.next();
+                    }
+
+                    result.push_str(self.history.get(number.checked_sub(1)?)?);
+                },
+                _ => result.push(character)
+            }
+        }
+
+        Some(result)
+    }
+}
+
+pub fn get(console: usize) -> &'static Shell {
+    &SHELLS[console]
+}
+
+pub fn start() {
+    for shell in SHELLS.iter() {
+        shell.start();
+    }
+}
+
+fn execute(input: &str) {
+    let expanded = SHELL_HISTORY.lock().expand(input);
+
+    match expanded {
+        Some(expanded) => {
+            if expanded != input {
+                println!("{}", expanded);
+            }
+
+            SHELL_HISTORY.lock().push(expanded.clone());
+
+            let mut command_runner = CommandRunner::new();
+
+            command_runner.run(&expanded);
+        },
+        None => error("History entry not found.")
+    }
 }
 
 pub fn print_info_message() {
     x86_64::instructions::interrupts::without_interrupts(|| {
-        WRITER.lock().change_color_code(ColorCode::new(Color::Yellow, Color::Black));
+        WRITER.lock().change_foreground(Color::Yellow);
     });
 
     println!("PlatiniumOS {}", OS_VERSION);
 
     x86_64::instructions::interrupts::without_interrupts(|| {
-        WRITER.lock().change_color_code(ColorCode::new(Color::White, Color::Black));
+        WRITER.lock().reset_color_code();
     });
 }
\ No newline at end of file
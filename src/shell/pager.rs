@@ -0,0 +1,189 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::ansi::{Action, Parser};
+use crate::input::{Key, KeyEvent};
+use crate::screen::{Canvas, FullScreen};
+
+// Columns a tab stop is apart, like on the console.
+const TAB_WIDTH: usize = 8;
+
+const SHORTCUTS: &str = "space next, b back, / search, n again, q quit";
+
+// Shows text a screen at a time, like more and less. Long lines are wrapped and escape
+// sequences left out, the text is shown in the plain colours.
+pub fn page(text: &str) {
+    let mut screen = FullScreen::enter();
+    let canvas = screen.canvas();
+
+    let mut pager = Pager {
+        lines: wrap(&strip_escapes(text), canvas.width()),
+        top: 0,
+        query: String::new(),
+        message: String::new()
+    };
+
+    canvas.set_cursor(None);
+
+    loop {
+        pager.draw(canvas);
+
+        let event = screen.read_key();
+
+        if !pager.handle(&mut screen, event) {
+            break;
+        }
+    }
+}
+
+struct Pager {
+    lines: Vec<String>,
+    // The first line shown.
+    top: usize,
+    query: String,
+    message: String
+}
+
+impl Pager {
+    // Returns false once the pager should close.
+    fn handle(&mut self, screen: &mut FullScreen, event: KeyEvent) -> bool {
+        let canvas = screen.canvas();
+        let page = text_rows(canvas) as isize;
+
+        self.message.clear();
+
+        if event.modifiers.ctrl || event.modifiers.alt {
+            return true;
+        }
+
+        match event.key {
+            Key::Char('q') | Key::Escape => return false,
+            Key::Char(' ') | Key::Char('f') | Key::PageDown => self.scroll(canvas, page),
+            Key::Char('b') | Key::PageUp => self.scroll(canvas, -page),
+            Key::Char('j') | Key::Enter | Key::ArrowDown => self.scroll(canvas, 1),
+            Key::Char('k') | Key::ArrowUp => self.scroll(canvas, -1),
+            Key::Char('g') | Key::Home => self.top = 0,
+            Key::Char('G') | Key::End => self.scroll(canvas, self.lines.len() as isize),
+            Key::Char('/') => {
+                let query = screen.read_line(canvas.height() - 1, "/", "");
+                canvas.set_cursor(None);
+
+                if let Some(query) = query.filter(|query| !query.is_empty()) {
+                    self.query = query;
+                    self.search(canvas);
+                }
+            },
+            Key::Char('n') if !self.query.is_empty() => self.search(canvas),
+            _ => { }
+        }
+
+        true
+    }
+
+    // Stops with the last line at the bottom of the screen.
+    fn scroll(&mut self, canvas: Canvas, lines: isize) {
+        let last = self.lines.len().saturating_sub(text_rows(canvas)) as isize;
+
+        self.top = (self.top as isize + lines).min(last).max(0) as usize;
+    }
+
+    // Brings the next line with a match to the top, going around to the start at the end.
+    fn search(&mut self, canvas: Canvas) {
+        let count = self.lines.len();
+
+        let found = (1..=count)
+            .map(|offset| (self.top + offset) % count)
+            .find(|index| self.lines[*index].contains(&self.query as &str));
+
+        match found {
+            Some(index) => {
+                self.top = index;
+                self.scroll(canvas, 0);
+            },
+            None => self.message = format!("\"{}\" not found", self.query)
+        }
+    }
+
+    fn draw(&self, canvas: Canvas) {
+        let color_code = canvas.default_color_code();
+        let highlight = color_code.inverted();
+        let rows = text_rows(canvas);
+
+        for row in 0..rows {
+            let line = match self.lines.get(self.top + row) {
+                Some(line) => line as &str,
+                None => "~"
+            };
+
+            canvas.fill_row(row, line, color_code);
+
+            if !self.query.is_empty() && self.top + row < self.lines.len() {
+                for (index, found) in line.match_indices(&self.query as &str) {
+                    canvas.text(row, line[..index].chars().count(), found, highlight);
+                }
+            }
+        }
+
+        let status = if self.message.is_empty() {
+            let bottom = (self.top + rows).min(self.lines.len());
+
+            format!(" lines {}-{} of {} ({}%)  {}",
+                    (self.top + 1).min(bottom),
+                    bottom,
+                    self.lines.len(),
+                    bottom * 100 / self.lines.len().max(1),
+                    SHORTCUTS)
+        } else {
+            format!(" {}", self.message)
+        };
+
+        canvas.fill_row(canvas.height() - 1, &status, highlight);
+    }
+}
+
+// All but the status row at the bottom.
+fn text_rows(canvas: Canvas) -> usize {
+    canvas.height().saturating_sub(1).max(1)
+}
+
+// Keeps only what would be printed, for output meant for the console.
+fn strip_escapes(text: &str) -> String {
+    let mut parser = Parser::new();
+    let bytes: Vec<u8> = text.bytes().filter_map(|byte| match parser.advance(byte) {
+        Some(Action::Print(byte)) => Some(byte),
+        _ => None
+    }).collect();
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+// Splits the text into screen rows, tabs expanded.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut rows = Vec::new();
+
+    for line in text.lines() {
+        let mut row = String::new();
+        let mut column = 0;
+
+        for character in line.chars() {
+            let (character, count) = match character {
+                '\t' => (' ', TAB_WIDTH - column % TAB_WIDTH),
+                character => (character, 1)
+            };
+
+            for _ in 0..count {
+                if column == width {
+                    rows.push(core::mem::take(&mut row));
+                    column = 0;
+                }
+
+                row.push(character);
+                column += 1;
+            }
+        }
+
+        rows.push(row);
+    }
+
+    rows
+}
\ No newline at end of file
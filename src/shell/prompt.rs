@@ -0,0 +1,255 @@
+use alloc::format;
+use alloc::string::String;
+use core::fmt::Write;
+use core::future::Future;
+use futures_util::stream::StreamExt;
+use x86_64::instructions::interrupts::without_interrupts;
+use crate::{console, pointer};
+use crate::input::{self, Key, KeyEvent};
+use crate::shell::{self, Shell, SHELL_HISTORY};
+use crate::shell::completion;
+use crate::shell::line_editor::LineEditor;
+use crate::vga_buffer;
+
+// Edits the line of the shell on the given console, while that console is shown.
+// Subscribes right away, so no keys get lost before the task is first polled.
+pub fn input_handler(console: usize) -> impl Future<Output = ()> {
+    let mut events = input::subscribe();
+    let shell = shell::get(console);
+
+    async move {
+        // Created on the first key after the prompt, so it starts right behind it.
+        let mut current: Option<LineEditor> = None;
+        let mut search: Option<Search> = None;
+
+        while let Some(event) = events.next().await {
+            if !event.is_pressed() || console::active() != console {
+                continue;
+            }
+
+            // Scrolling back works while a command is still printing, too.
+            if event.modifiers.shift && scroll(shell, event.key) {
+                continue;
+            }
+
+            if !shell.awaits_input() {
+                continue;
+            }
+
+            let editor = current.get_or_insert_with(|| LineEditor::new(console::writer(console)));
+
+            if let Some(active) = search.as_mut() {
+                match active.handle(editor, event) {
+                    SearchStep::Continue => continue,
+                    SearchStep::Cancelled => {
+                        search = None;
+                        continue;
+                    },
+                    // The key that ended the search is handled like any other.
+                    SearchStep::Accepted => search = None
+                }
+            }
+
+            if event.key == Key::Enter {
+                let line = editor.finish();
+                current = None;
+
+                write(shell, "\n");
+                shell.run(line);
+            } else if event.key == Key::Char('r') && event.modifiers.ctrl {
+                search = Some(Search::start(editor));
+            } else {
+                edit(shell, editor, event);
+            }
+        }
+    }
+}
+
+fn write(shell: &Shell, text: &str) {
+    without_interrupts(|| {
+        console::writer(shell.console()).lock().write_str(text).unwrap();
+    });
+}
+
+fn scroll(shell: &Shell, key: Key) -> bool {
+    let page = vga_buffer::height() as isize - 1;
+
+    let lines = match key {
+        Key::PageUp => page,
+        Key::PageDown => -page,
+        _ => return false
+    };
+
+    without_interrupts(|| console::writer(shell.console()).lock().scroll_view(lines));
+
+    true
+}
+
+enum SearchStep {
+    Continue,
+    Accepted,
+    Cancelled
+}
+
+// Ctrl-R reverse incremental search. Typing narrows the query, Ctrl-R again jumps to
+// an older match, Escape or Ctrl-G gives back the line from before the search.
+struct Search {
+    query: String,
+    found: Option<usize>,
+    original: String
+}
+
+impl Search {
+    fn start(editor: &mut LineEditor) -> Search {
+        let search = Search {
+            query: String::new(),
+            found: None,
+            original: editor.line()
+        };
+
+        search.show(editor, false);
+
+        search
+    }
+
+    fn handle(&mut self, editor: &mut LineEditor, event: KeyEvent) -> SearchStep {
+        let modifiers = event.modifiers;
+
+        match event.key {
+            Key::Char('r') if modifiers.ctrl => {
+                let before = self.found.unwrap_or_else(|| SHELL_HISTORY.lock().history.len());
+                self.find(editor, before);
+            },
+            Key::Char('g') if modifiers.ctrl => return self.cancel(editor),
+            Key::Escape => return self.cancel(editor),
+            Key::Char(character) if !modifiers.ctrl && !modifiers.alt => {
+                if self.query.len() < vga_buffer::width() {
+                    self.query.push(character);
+                }
+
+                // The current match may still fit the longer query.
+                let before = match self.found {
+                    Some(index) => index + 1,
+                    None => SHELL_HISTORY.lock().history.len()
+                };
+                self.find(editor, before);
+            },
+            Key::Backspace => {
+                self.query.pop();
+
+                let before = SHELL_HISTORY.lock().history.len();
+                self.find(editor, before);
+            },
+            _ => {
+                editor.set_label("");
+
+                if let Some(index) = self.found {
+                    SHELL_HISTORY.lock().index = index as u64;
+                }
+
+                return SearchStep::Accepted;
+            }
+        }
+
+        SearchStep::Continue
+    }
+
+    fn find(&mut self, editor: &mut LineEditor, before: usize) {
+        let found = SHELL_HISTORY.lock().search(&self.query, before);
+
+        match found {
+            Some(index) => {
+                let entry = SHELL_HISTORY.lock().history[index].clone();
+
+                self.found = Some(index);
+                editor.set_line(&entry);
+                self.show(editor, false);
+            },
+            None => self.show(editor, !self.query.is_empty())
+        }
+    }
+
+    fn cancel(&self, editor: &mut LineEditor) -> SearchStep {
+        editor.set_label("");
+        editor.set_line(&self.original);
+
+        SearchStep::Cancelled
+    }
+
+    fn show(&self, editor: &mut LineEditor, failing: bool) {
+        let prefix = if failing { "failing " } else { "" };
+
+        editor.set_label(&format!("({}reverse-i-search)`{}': ", prefix, self.query));
+    }
+}
+
+fn edit(shell: &Shell, editor: &mut LineEditor, event: KeyEvent) {
+    let modifiers = event.modifiers;
+
+    match event.key {
+        Key::Char(character) if modifiers.ctrl => match character.to_ascii_lowercase() {
+            'a' => editor.move_home(),
+            'e' => editor.move_end(),
+            'b' => editor.move_left(),
+            'f' => editor.move_right(),
+            'd' => editor.delete(),
+            'k' => editor.kill_to_end(),
+            'u' => editor.kill_to_start(),
+            'w' => editor.kill_previous_word(),
+            'y' => editor.yank(),
+            _ => { }
+        },
+        Key::Char(character) if modifiers.alt => match character.to_ascii_lowercase() {
+            'b' => editor.move_word_left(),
+            'f' => editor.move_word_right(),
+            'd' => editor.kill_next_word(),
+            _ => { }
+        },
+        Key::Char(character) => editor.insert(character),
+        Key::ArrowLeft if modifiers.ctrl => editor.move_word_left(),
+        Key::ArrowRight if modifiers.ctrl => editor.move_word_right(),
+        Key::ArrowLeft => editor.move_left(),
+        Key::ArrowRight => editor.move_right(),
+        Key::Home => editor.move_home(),
+        Key::End => editor.move_end(),
+        Key::Backspace => editor.backspace(),
+        Key::Delete => editor.delete(),
+        Key::Tab => complete(shell, editor),
+        // Pastes what was last selected with the mouse, on a single line.
+        Key::Insert if modifiers.shift => editor.insert_str(&pointer::paste_buffer().replace('\n', " ")),
+        Key::ArrowUp => {
+            let entry = SHELL_HISTORY.lock().previous();
+
+            if let Some(entry) = entry {
+                editor.set_line(&entry);
+            }
+        },
+        Key::ArrowDown => {
+            let entry = SHELL_HISTORY.lock().next();
+
+            if let Some(entry) = entry {
+                editor.set_line(&entry);
+            }
+        },
+        _ => { }
+    }
+}
+
+// Completes as far as the candidates agree. When that doesn't get any further, lists
+// them below and redraws the line after a fresh prompt.
+fn complete(shell: &Shell, editor: &mut LineEditor) {
+    let completion = match completion::complete(editor.contents(), editor.cursor()) {
+        Some(completion) => completion,
+        None => return
+    };
+
+    let word: String = editor.contents()[completion.start..editor.cursor()].iter().collect();
+
+    if completion.replacement != word {
+        editor.replace(completion.start, &completion.replacement);
+    } else if 1 < completion.candidates.len() {
+        editor.finish();
+        write(shell, &format!("\n{}\n> ", completion.candidates.join("  ")));
+        editor.redraw();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,39 @@
+use alloc::format;
+use core::time::Duration;
+use crate::{allocator, console, rtc, vga_buffer};
+use crate::task::{keyboard, registry, timer};
+use crate::task::registry::TaskState;
+
+const REFRESH: Duration = Duration::from_secs(1);
+
+// Keeps the status bar at the bottom of the screen up to date.
+pub async fn run() {
+    loop {
+        draw();
+        timer::sleep(REFRESH).await;
+    }
+}
+
+fn draw() {
+    let time = rtc::now();
+    let uptime = timer::uptime().as_secs();
+
+    let tasks = registry::snapshot().iter()
+        .filter(|task| task.state != TaskState::Finished && task.state != TaskState::Cancelled)
+        .count();
+
+    let text = format!(" {:02}:{:02}:{:02}  up {}:{:02}:{:02}  heap {}/{} KiB  {} tasks  {}  tty{}",
+                       time.hours,
+                       time.minutes,
+                       time.seconds,
+                       uptime / 3600,
+                       uptime / 60 % 60,
+                       uptime % 60,
+                       allocator::used() / 1024,
+                       allocator::HEAP_SIZE / 1024,
+                       tasks,
+                       keyboard::layout().as_str(),
+                       console::active() + 1);
+
+    vga_buffer::draw_status_line(&text);
+}
\ No newline at end of file
@@ -1,85 +1,310 @@
-use super::{Task, TaskId};
-use alloc::collections::BTreeMap;
+use super::{registry, Priority, Task, TaskId};
+use super::join_handle::{joinable, JoinHandle};
+use super::registry::TaskState;
+use crate::thread;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
 use alloc::sync::Arc;
+use core::arch::x86_64::_rdtsc;
+use core::future::Future;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use core::task::{Context, Poll, Waker};
 use crossbeam_queue::ArrayQueue;
 use alloc::task::Wake;
+use spin::{Mutex, RwLock};
+use x86_64::instructions::interrupts::without_interrupts;
+
+pub const DEFAULT_TASK_QUEUE_SIZE: usize = 100;
+
+static DROPPED_WAKEUPS: AtomicU64 = AtomicU64::new(0);
+
+pub fn dropped_wakeups() -> u64 {
+    DROPPED_WAKEUPS.load(Ordering::Relaxed)
+}
 
 pub struct Executor {
     tasks: BTreeMap<TaskId, Task>,
-    task_queue: Arc<ArrayQueue<TaskId>>,
-    waker_cache: BTreeMap<TaskId, Waker>
+    task_queue: Arc<TaskQueue>,
+    waker_cache: BTreeMap<TaskId, Arc<TaskWaker>>,
+    spawner: Spawner,
+    budgets: [usize; Priority::LEVELS]
 }
 
 impl Executor {
     pub fn new() -> Executor {
+        Executor::with_queue_size(DEFAULT_TASK_QUEUE_SIZE)
+    }
+
+    pub fn with_queue_size(queue_size: usize) -> Executor {
         Executor {
             tasks: BTreeMap::new(),
-            task_queue: Arc::new(ArrayQueue::new(100)),
-            waker_cache: BTreeMap::new()
+            task_queue: Arc::new(TaskQueue::new(queue_size)),
+            waker_cache: BTreeMap::new(),
+            spawner: Spawner::new(),
+            budgets: Priority::ALL.map(Priority::budget)
         }
     }
 
-    pub fn spawn(&mut self, task: Task) {
+    pub fn spawner(&self) -> Spawner {
+        self.spawner.clone()
+    }
+
+    fn spawn_task(&mut self, task: Task) {
         let task_id = task.id;
 
         if self.tasks.insert(task.id, task).is_some() {
             panic!("Task with same ID already exists.");
         }
 
-        self.task_queue.push(task_id).expect("Queue full.");
+        self.task_queue.reserve(self.tasks.len());
+
+        let task = &self.tasks[&task_id];
+        let waker = TaskWaker::new(task_id, task.priority, self.task_queue.clone());
+
+        registry::register(task_id, task.name.clone(), task.priority, waker.queued.clone(), task.abort.clone());
+
+        waker.wake_task();
+
+        self.waker_cache.insert(task_id, waker);
     }
 
+    // Gives the CPU to the other threads whenever there is nothing to poll.
     pub fn run(&mut self) -> ! {
         loop {
-            self.run_ready_tasks();
+            let spawned = self.accept_spawned_tasks();
+            let polled = self.run_ready_tasks();
+
+            if !spawned && !polled {
+                thread::yield_now();
+            }
+        }
+    }
+
+    // Returns whether there were any.
+    fn accept_spawned_tasks(&mut self) -> bool {
+        let mut spawned = false;
+
+        while let Some(task) = self.spawner.pop() {
+            self.spawn_task(task);
+            spawned = true;
         }
+
+        spawned
     }
 
-    fn run_ready_tasks(&mut self) {
-        let Self {
-            tasks,
-            task_queue,
-            waker_cache
-        } = self;
+    // Picks the next task from the highest priority queue that still has budget left.
+    // Once every level with work has used up its budget, all budgets are refilled, so
+    // lower priorities get a share of the polls even when higher ones are always busy.
+    fn next_task(&mut self) -> Option<TaskId> {
+        for _ in 0..2 {
+            for priority in Priority::ALL {
+                let budget = &mut self.budgets[priority.index()];
+
+                if *budget == 0 {
+                    continue;
+                }
+
+                if let Some(task_id) = self.task_queue.pop(priority) {
+                    *budget -= 1;
+                    return Some(task_id);
+                }
+            }
+
+            self.budgets = Priority::ALL.map(Priority::budget);
+        }
+
+        None
+    }
+
+    // Returns whether any task was polled.
+    fn run_ready_tasks(&mut self) -> bool {
+        if self.task_queue.take_overflow() {
+            // A wakeup was lost, so we don't know who is ready. Polling everything is safe.
+            for waker in self.waker_cache.values() {
+                waker.queued.store(false, Ordering::Release);
+                waker.wake_task();
+            }
+        }
+
+        // Return to run() once in a while to pick up newly spawned tasks, even if
+        // some task keeps waking itself up.
+        let round: usize = Priority::ALL.iter().map(|priority| priority.budget()).sum();
+
+        let mut polled = false;
+
+        for _ in 0..round {
+            let task_id = match self.next_task() {
+                Some(task_id) => task_id,
+                None => return polled
+            };
+
+            polled = true;
+
+            let Self {
+                tasks,
+                waker_cache,
+                ..
+            } = self;
 
-        while let Ok(task_id) = task_queue.pop() {
             let task = match tasks.get_mut(&task_id) {
                 Some(task) => task,
                 None => continue
             };
 
-            let waker = waker_cache
-                .entry(task_id)
-                .or_insert_with(|| TaskWaker::new(task_id, task_queue.clone()));
-            let mut context = Context::from_waker(waker);
+            let task_waker = match waker_cache.get(&task_id) {
+                Some(task_waker) => task_waker,
+                None => continue
+            };
+
+            task_waker.queued.store(false, Ordering::Release);
+
+            if task.abort.is_aborted() {
+                tasks.remove(&task_id);
+                waker_cache.remove(&task_id);
+                registry::finish(task_id, TaskState::Cancelled);
+                continue;
+            }
+
+            let waker = Waker::from(task_waker.clone());
+            let mut context = Context::from_waker(&waker);
 
-            match task.poll(&mut context) {
+            registry::start_poll(task_id);
+            let start = unsafe { _rdtsc() };
+
+            let result = task.poll(&mut context);
+
+            registry::end_poll(task_id, unsafe { _rdtsc() } - start);
+
+            match result {
                 Poll::Ready(()) => {
                     tasks.remove(&task_id);
                     waker_cache.remove(&task_id);
+                    registry::finish(task_id, TaskState::Finished);
                 },
                 Poll::Pending => { }
             }
         }
+
+        polled
+    }
+}
+
+#[derive(Clone)]
+pub struct Spawner {
+    new_tasks: Arc<Mutex<VecDeque<Task>>>
+}
+
+impl Spawner {
+    fn new() -> Spawner {
+        Spawner {
+            new_tasks: Arc::new(Mutex::new(VecDeque::new()))
+        }
+    }
+
+    pub fn spawn_named<F>(&self, name: &str, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static
+    {
+        self.spawn_prioritized(name, Priority::Normal, future)
+    }
+
+    pub fn spawn_prioritized<F>(&self, name: &str, priority: Priority, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static
+    {
+        let (task, handle) = joinable(Some(String::from(name)), priority, future);
+        self.new_tasks.lock().push_back(task);
+
+        handle
+    }
+
+    fn pop(&self) -> Option<Task> {
+        self.new_tasks.lock().pop_front()
+    }
+}
+
+// One run queue per priority. Wakers push into them from interrupt handlers, so
+// they must not allocate there. They only grow from the executor, with interrupts disabled.
+// The locks are always taken with interrupts disabled, so a thread preempted while
+// holding one can't keep the executor spinning with the timer off.
+struct TaskQueue {
+    queues: [RwLock<ArrayQueue<TaskId>>; Priority::LEVELS],
+    overflowed: AtomicBool
+}
+
+impl TaskQueue {
+    fn new(capacity: usize) -> TaskQueue {
+        TaskQueue {
+            queues: Priority::ALL.map(|_| RwLock::new(ArrayQueue::new(capacity))),
+            overflowed: AtomicBool::new(false)
+        }
+    }
+
+    fn push(&self, task_id: TaskId, priority: Priority) {
+        let pushed = without_interrupts(|| self.queues[priority.index()].read().push(task_id));
+
+        if pushed.is_err() {
+            DROPPED_WAKEUPS.fetch_add(1, Ordering::Relaxed);
+            self.overflowed.store(true, Ordering::Release);
+        }
+    }
+
+    fn pop(&self, priority: Priority) -> Option<TaskId> {
+        without_interrupts(|| self.queues[priority.index()].read().pop().ok())
+    }
+
+    fn take_overflow(&self) -> bool {
+        self.overflowed.swap(false, Ordering::AcqRel)
+    }
+
+    // Wakeups are deduplicated, so queues with room for every task never overflow.
+    fn reserve(&self, task_count: usize) {
+        for queue in self.queues.iter() {
+            without_interrupts(|| {
+                let mut queue = queue.write();
+
+                if task_count <= queue.capacity() {
+                    return;
+                }
+
+                let grown = ArrayQueue::new(task_count.max(queue.capacity() * 2));
+
+                while let Ok(task_id) = queue.pop() {
+                    grown.push(task_id).expect("Grown task queue too small.");
+                }
+
+                *queue = grown;
+            });
+        }
     }
 }
 
 struct TaskWaker {
     task_id: TaskId,
-    task_queue: Arc<ArrayQueue<TaskId>>
+    priority: Priority,
+    queued: Arc<AtomicBool>,
+    task_queue: Arc<TaskQueue>
 }
 
 impl TaskWaker {
-    fn new(task_id: TaskId, task_queue: Arc<ArrayQueue<TaskId>>) -> Waker {
-        Waker::from(Arc::new(TaskWaker {
+    fn new(task_id: TaskId, priority: Priority, task_queue: Arc<TaskQueue>) -> Arc<TaskWaker> {
+        Arc::new(TaskWaker {
             task_id,
+            priority,
+            queued: Arc::new(AtomicBool::new(false)),
             task_queue
-        }))
+        })
     }
 
     fn wake_task(&self) {
-        self.task_queue.push(self.task_id).expect("Task queue full.");
+        // Task already waiting in the queue, it will be polled anyway.
+        if self.queued.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        self.task_queue.push(self.task_id, self.priority);
     }
 }
 
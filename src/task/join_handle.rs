@@ -0,0 +1,138 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+use futures_util::task::AtomicWaker;
+use spin::Mutex;
+use super::{Priority, Task};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinError {
+    Cancelled
+}
+
+struct AbortState {
+    aborted: AtomicBool,
+    task_waker: AtomicWaker
+}
+
+// Type-erased way to cancel a task, shared by the task and the task registry.
+#[derive(Clone)]
+pub struct AbortHandle {
+    state: Arc<AbortState>
+}
+
+impl AbortHandle {
+    pub(crate) fn new() -> AbortHandle {
+        AbortHandle {
+            state: Arc::new(AbortState {
+                aborted: AtomicBool::new(false),
+                task_waker: AtomicWaker::new()
+            })
+        }
+    }
+
+    // Cancels the task. The executor drops it the next time it would be polled.
+    pub fn abort(&self) {
+        self.state.aborted.store(true, Ordering::Release);
+        self.state.task_waker.wake();
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.state.aborted.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn register(&self, waker: &Waker) {
+        self.state.task_waker.register(waker);
+    }
+}
+
+struct JoinState<T> {
+    result: Mutex<Option<Result<T, JoinError>>>,
+    join_waker: AtomicWaker
+}
+
+impl<T> JoinState<T> {
+    fn new() -> JoinState<T> {
+        JoinState {
+            result: Mutex::new(None),
+            join_waker: AtomicWaker::new()
+        }
+    }
+
+    fn finish(&self, result: Result<T, JoinError>) {
+        *self.result.lock() = Some(result);
+        self.join_waker.wake();
+    }
+}
+
+// Awaiting the handle of an aborted task resolves with JoinError::Cancelled.
+pub struct JoinHandle<T> {
+    state: Arc<JoinState<T>>
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        if let Some(result) = self.state.result.lock().take() {
+            return Poll::Ready(result);
+        }
+
+        self.state.join_waker.register(cx.waker());
+
+        match self.state.result.lock().take() {
+            Some(result) => Poll::Ready(result),
+            None => Poll::Pending
+        }
+    }
+}
+
+struct Joinable<F: Future> {
+    future: Pin<Box<F>>,
+    state: Arc<JoinState<F::Output>>,
+    done: bool
+}
+
+impl<F: Future> Future for Joinable<F> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        match self.future.as_mut().poll(cx) {
+            Poll::Ready(output) => {
+                self.done = true;
+                self.state.finish(Ok(output));
+                Poll::Ready(())
+            },
+            Poll::Pending => Poll::Pending
+        }
+    }
+}
+
+impl<F: Future> Drop for Joinable<F> {
+    fn drop(&mut self) {
+        // Dropped before completing, so the executor cancelled it.
+        if !self.done {
+            self.state.finish(Err(JoinError::Cancelled));
+        }
+    }
+}
+
+pub(crate) fn joinable<F>(name: Option<String>, priority: Priority, future: F) -> (Task, JoinHandle<F::Output>)
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static
+{
+    let state = Arc::new(JoinState::new());
+
+    let task = Task::with_options(name, priority, Joinable {
+        future: Box::pin(future),
+        state: state.clone(),
+        done: false
+    });
+
+    (task, JoinHandle { state })
+}
\ No newline at end of file
@@ -1,123 +1,294 @@
+use alloc::collections::BTreeMap;
 use conquer_once::spin::OnceCell;
 use crossbeam_queue::ArrayQueue;
-use crate::{print, shell, warning, WRITER};
+use crate::input::{self, Key, KeyEvent, KeyState, Modifiers};
 use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 use core::task::{Poll, Context};
 use futures_util::stream::{Stream, StreamExt};
 use futures_util::task::AtomicWaker;
-use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, ScancodeSet1};
-use crate::reading::READER;
-use crate::shell::SHELL_HISTORY;
+use pc_keyboard::{layouts, DecodedKey, HandleControl, KeyCode, Keyboard, KeyboardLayout, KeyState as RawKeyState, ScancodeSet1};
+use pc_keyboard::Modifiers as RawModifiers;
+use crate::ps2::{self, ACKNOWLEDGE, RESEND};
 
 static WAKER: AtomicWaker = AtomicWaker::new();
+static DROPPED_SCANCODES: AtomicU64 = AtomicU64::new(0);
+static LAYOUT: AtomicU8 = AtomicU8::new(Layout::Us as u8);
 pub static SCANCODE_QUEUE: OnceCell<ArrayQueue<u8>> = OnceCell::uninit();
 
+pub const SCANCODE_QUEUE_SIZE: usize = 100;
+
+const SET_LEDS: u8 = 0xED;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Layout {
+    Us,
+    Uk,
+    German,
+    Azerty,
+    Dvorak,
+    PolishProgrammer
+}
+
+impl Layout {
+    pub const ALL: [Layout; 6] = [Layout::Us, Layout::Uk, Layout::German, Layout::Azerty, Layout::Dvorak, Layout::PolishProgrammer];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Layout::Us => "us",
+            Layout::Uk => "uk",
+            Layout::German => "de",
+            Layout::Azerty => "azerty",
+            Layout::Dvorak => "dvorak",
+            Layout::PolishProgrammer => "pl"
+        }
+    }
+
+    pub fn from_str(name: &str) -> Option<Layout> {
+        Layout::ALL.iter().copied().find(|layout| layout.as_str() == name)
+    }
+}
+
+pub fn layout() -> Layout {
+    let index = LAYOUT.load(Ordering::Relaxed) as usize;
+    Layout::ALL[index]
+}
+
+// Takes effect with the next key, keys held right now still release as what they were.
+pub fn set_layout(layout: Layout) {
+    LAYOUT.store(layout as u8, Ordering::Relaxed);
+}
+
+// pc-keyboard fixes the layout in the Keyboard's type, so this one looks up whichever
+// layout is selected right now and maps the key with it.
+struct ActiveLayout;
+
+impl KeyboardLayout for ActiveLayout {
+    fn map_keycode(keycode: KeyCode, modifiers: &RawModifiers, handle_ctrl: HandleControl) -> DecodedKey {
+        match layout() {
+            Layout::Us => layouts::Us104Key::map_keycode(keycode, modifiers, handle_ctrl),
+            Layout::Uk => layouts::Uk105Key::map_keycode(keycode, modifiers, handle_ctrl),
+            Layout::German => layouts::De105Key::map_keycode(keycode, modifiers, handle_ctrl),
+            Layout::Azerty => layouts::Azerty::map_keycode(keycode, modifiers, handle_ctrl),
+            Layout::Dvorak => layouts::Dvorak104Key::map_keycode(keycode, modifiers, handle_ctrl),
+            Layout::PolishProgrammer => map_polish_programmer(keycode, modifiers, handle_ctrl)
+        }
+    }
+}
+
+// US layout with the Polish letters on AltGr, which pc-keyboard doesn't ship.
+fn map_polish_programmer(keycode: KeyCode, modifiers: &RawModifiers, handle_ctrl: HandleControl) -> DecodedKey {
+    if modifiers.alt_gr {
+        let letters = match keycode {
+            KeyCode::A => Some(('ą', 'Ą')),
+            KeyCode::C => Some(('ć', 'Ć')),
+            KeyCode::E => Some(('ę', 'Ę')),
+            KeyCode::L => Some(('ł', 'Ł')),
+            KeyCode::N => Some(('ń', 'Ń')),
+            KeyCode::O => Some(('ó', 'Ó')),
+            KeyCode::S => Some(('ś', 'Ś')),
+            KeyCode::X => Some(('ź', 'Ź')),
+            KeyCode::Z => Some(('ż', 'Ż')),
+            _ => None
+        };
+
+        if let Some((lower, upper)) = letters {
+            return DecodedKey::Unicode(if modifiers.is_caps() { upper } else { lower });
+        }
+    }
+
+    layouts::Us104Key::map_keycode(keycode, modifiers, handle_ctrl)
+}
+
+pub fn dropped_scancodes() -> u64 {
+    DROPPED_SCANCODES.load(Ordering::Relaxed)
+}
+
+// Runs in the interrupt handler, so dropped scancodes are only counted. Printing from
+// here could spin on a console lock held by the interrupted thread.
 pub fn add_scancode(scancode: u8) {
     if let Ok(queue) = SCANCODE_QUEUE.try_get() {
         if let Err(_) = queue.push(scancode) {
-            warning("WARNING: Scancode queue full. Dropping keyboard input.");
+            DROPPED_SCANCODES.fetch_add(1, Ordering::Relaxed);
         } else {
             WAKER.wake();
         }
     } else {
-        warning("WARNING: Scancode queue uninitialized.");
+        DROPPED_SCANCODES.fetch_add(1, Ordering::Relaxed);
     }
 }
 
-pub async fn input_handler() {
+// Turns scancodes into key events for whoever subscribed to them in the input module.
+// Ctrl and Alt combinations arrive as the plain key with the modifier set, so the
+// layout is told to leave letters alone when Ctrl is held.
+pub async fn handle_scancodes() {
     let mut scancodes = ScancodeStream::new();
-    let mut keyboard = Keyboard::new(layouts::Us104Key, ScancodeSet1,
+    let mut keyboard = Keyboard::new(ActiveLayout, ScancodeSet1,
                                      HandleControl::Ignore);
+
+    let mut modifiers = Modifiers {
+        num_lock: true,
+        ..Modifiers::default()
+    };
+
+    let mut held = HeldModifiers::default();
+
+    // What each held key meant when it went down, so its release reports the same key.
+    let mut pressed: BTreeMap<KeyCode, Key> = BTreeMap::new();
+
+    let mut led_command = LedCommand::Idle;
+    led_command.set(leds(&modifiers));
+
     while let Some(scancode) = scancodes.next().await {
-        if let Ok(Some(key_event)) = keyboard.add_byte(scancode) {
-            if let Some(key) = keyboard.process_keyevent(key_event) {
-                if scancode == 0x4B {
-                    // Handle arrow left
-                    x86_64::instructions::interrupts::without_interrupts(|| {
-                        WRITER.lock().move_left();
-                    });
-                } else if scancode == 0x4D {
-                    // Handle arrow right
-                    x86_64::instructions::interrupts::without_interrupts(|| {
-                        WRITER.lock().move_right();
-                    });
-                } else if scancode == 0x48 {
-                    // Handle arrow up
-                    let history = shell::SHELL_HISTORY.lock().history.clone();
-
-                    if history.len() > 0 {
-                        let mut index = shell::SHELL_HISTORY.lock().index;
-
-                        if index == 0 {
-                            return;
-                        }
-
-                        index -= 1;
-
-                        shell::SHELL_HISTORY.lock().index = index;
-
-                        let history = history[index as usize].clone();
-
-                        x86_64::instructions::interrupts::without_interrupts(|| {
-                            let row = WRITER.lock().row_position;
-                            WRITER.lock().clear_row(row);
-                            WRITER.lock().column_position = 0;
-                            print!("> {}", history);
-                            READER.lock().column_position_start = 2;
-                        });
-
-                        shell::SHELL_HISTORY.lock().index = index;
-                    }
-                } else if scancode == 0x50 {
-                    // Handle arrow down
-                    let history = shell::SHELL_HISTORY.lock().history.clone();
-
-                    if history.len() > 0 {
-                        let mut index = shell::SHELL_HISTORY.lock().index;
-
-                        if history.len() as u64 - 1 == index {
-                            return;
-                        }
-
-                        index += 1;
-                        shell::SHELL_HISTORY.lock().index = index;
-
-                        let history = history[index as usize].clone();
-
-                        x86_64::instructions::interrupts::without_interrupts(|| {
-                            let row = WRITER.lock().row_position;
-                            WRITER.lock().clear_row(row);
-                            WRITER.lock().column_position = 0;
-                            print!("> {}", history);
-                            READER.lock().column_position_start = 2;
-                        });
-
-                        shell::SHELL_HISTORY.lock().index = index;
-                    }
-                } else if scancode == 0x0E && READER.lock().awaits_input {
-                    // Handle backspace
-                    x86_64::instructions::interrupts::without_interrupts(|| {
-                        WRITER.lock().backspace();
-                    });
-                } else {
-                    if READER.lock().awaits_input {
-                        match key {
-                            DecodedKey::Unicode(character) => print!("{}", character),
-                            DecodedKey::RawKey(key) => print!("{:?}", key)
-                        }
-
-                        if scancode == 0x1C {
-                            // Handle Enter
-                            READER.lock().awaits_input = false;
-                            shell::run();
-                        }
-                    }
+        match scancode {
+            ACKNOWLEDGE => {
+                led_command.acknowledged();
+                continue;
+            },
+            RESEND => {
+                led_command.resend();
+                continue;
+            },
+            _ => { }
+        }
+
+        if let Ok(Some(raw_event)) = keyboard.add_byte(scancode) {
+            let code = raw_event.code;
+            let state = match raw_event.state {
+                RawKeyState::Down => KeyState::Pressed,
+                RawKeyState::Up => KeyState::Released
+            };
+
+            let repeated = state == KeyState::Pressed && pressed.contains_key(&code);
+
+            let is_lock = match code {
+                KeyCode::CapsLock | KeyCode::NumpadLock | KeyCode::ScrollLock => true,
+                _ => false
+            };
+
+            if !repeated {
+                update_modifiers(&mut modifiers, &mut held, code, state);
+
+                if is_lock && state == KeyState::Pressed {
+                    led_command.set(leds(&modifiers));
                 }
             }
+
+            // pc-keyboard toggles its own Caps and Num Lock on every press, repeats
+            // included, so those never reach it to keep both in step.
+            let decoded = if repeated && is_lock {
+                None
+            } else {
+                keyboard.process_keyevent(raw_event)
+            };
+
+            let key = match state {
+                KeyState::Pressed => {
+                    let key = decode(code, decoded);
+                    pressed.insert(code, key);
+                    key
+                },
+                KeyState::Released => pressed.remove(&code)
+                    .unwrap_or_else(|| Key::from_code(code).unwrap_or(Key::Other(code)))
+            };
+
+            input::publish(KeyEvent {
+                code,
+                key,
+                modifiers,
+                state
+            });
+        }
+    }
+}
+
+fn decode(code: KeyCode, decoded: Option<DecodedKey>) -> Key {
+    match decoded {
+        Some(DecodedKey::Unicode(character)) => Key::from_code(code).unwrap_or_else(|| Key::from_char(character)),
+        Some(DecodedKey::RawKey(raw)) => Key::from_code(raw).unwrap_or(Key::Other(raw)),
+        None => Key::from_code(code).unwrap_or(Key::Other(code))
+    }
+}
+
+// Shift and Ctrl exist on both sides, each stays on until both keys are up.
+#[derive(Default)]
+struct HeldModifiers {
+    shift_left: bool,
+    shift_right: bool,
+    control_left: bool,
+    control_right: bool
+}
+
+fn update_modifiers(modifiers: &mut Modifiers, held: &mut HeldModifiers, code: KeyCode, state: KeyState) {
+    let pressed = state == KeyState::Pressed;
+
+    match code {
+        KeyCode::ShiftLeft => held.shift_left = pressed,
+        KeyCode::ShiftRight => held.shift_right = pressed,
+        KeyCode::ControlLeft => held.control_left = pressed,
+        KeyCode::ControlRight => held.control_right = pressed,
+        KeyCode::AltLeft => modifiers.alt = pressed,
+        KeyCode::AltRight => modifiers.alt_gr = pressed,
+        KeyCode::CapsLock if pressed => modifiers.caps_lock = !modifiers.caps_lock,
+        KeyCode::NumpadLock if pressed => modifiers.num_lock = !modifiers.num_lock,
+        KeyCode::ScrollLock if pressed => modifiers.scroll_lock = !modifiers.scroll_lock,
+        _ => { }
+    }
+
+    modifiers.shift = held.shift_left || held.shift_right;
+    modifiers.ctrl = held.control_left || held.control_right;
+}
+
+// Setting the LEDs takes two bytes, the second is only sent once the keyboard
+// acknowledged the command. Changes while one is under way wait for it to finish, a
+// second command byte would be taken for the LEDs of the first.
+enum LedCommand {
+    Idle,
+    // The command byte was sent, the LEDs follow once it is acknowledged.
+    Command(u8),
+    // The LEDs were sent, with what to set after them, if they changed meanwhile.
+    Leds { sent: u8, next: Option<u8> }
+}
+
+impl LedCommand {
+    fn set(&mut self, leds: u8) {
+        match self {
+            LedCommand::Idle => {
+                ps2::write_data(SET_LEDS);
+                *self = LedCommand::Command(leds);
+            },
+            LedCommand::Command(pending) => *pending = leds,
+            LedCommand::Leds { next, .. } => *next = Some(leds)
+        }
+    }
+
+    fn acknowledged(&mut self) {
+        *self = match *self {
+            LedCommand::Idle => LedCommand::Idle,
+            LedCommand::Command(leds) => {
+                ps2::write_data(leds);
+                LedCommand::Leds { sent: leds, next: None }
+            },
+            LedCommand::Leds { next: None, .. } => LedCommand::Idle,
+            LedCommand::Leds { next: Some(leds), .. } => {
+                ps2::write_data(SET_LEDS);
+                LedCommand::Command(leds)
+            }
+        };
+    }
+
+    fn resend(&self) {
+        match *self {
+            LedCommand::Idle => { },
+            LedCommand::Command(_) => ps2::write_data(SET_LEDS),
+            LedCommand::Leds { sent, .. } => ps2::write_data(sent)
         }
     }
+}
 
-    print!("!");
+fn leds(modifiers: &Modifiers) -> u8 {
+    (modifiers.scroll_lock as u8) | (modifiers.num_lock as u8) << 1 | (modifiers.caps_lock as u8) << 2
 }
 
 pub struct ScancodeStream {
@@ -1,11 +1,71 @@
 use core::future::Future;
 use core::pin::Pin;
 use alloc::boxed::Box;
+use alloc::string::String;
 use core::task::{Context, Poll};
 use core::sync::atomic::{AtomicU64, Ordering};
+use conquer_once::spin::OnceCell;
+use crate::task::executor::Spawner;
+use crate::task::join_handle::{AbortHandle, JoinHandle};
 
 pub mod keyboard;
+pub mod mouse;
 pub(crate) mod executor;
+pub mod join_handle;
+pub mod registry;
+pub mod timer;
+pub mod sync;
+
+pub static SPAWNER: OnceCell<Spawner> = OnceCell::uninit();
+
+pub fn spawn_named<F>(name: &str, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static
+{
+    SPAWNER.try_get().expect("Spawner not initialized.").spawn_named(name, future)
+}
+
+pub fn spawn_prioritized<F>(name: &str, priority: Priority, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static
+{
+    SPAWNER.try_get().expect("Spawner not initialized.").spawn_prioritized(name, priority, future)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    High,
+    Normal,
+    Low
+}
+
+impl Priority {
+    pub const LEVELS: usize = 3;
+    pub const ALL: [Priority; Priority::LEVELS] = [Priority::High, Priority::Normal, Priority::Low];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    // How many polls in a row this level gets before lower levels have their turn.
+    fn budget(self) -> usize {
+        match self {
+            Priority::High => 8,
+            Priority::Normal => 4,
+            Priority::Low => 2
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::High => "high",
+            Priority::Normal => "normal",
+            Priority::Low => "low"
+        }
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 struct TaskId(u64);
@@ -20,18 +80,25 @@ impl TaskId {
 
 pub struct Task {
     id: TaskId,
-    future: Pin<Box<dyn Future<Output = ()>>>
+    name: Option<String>,
+    priority: Priority,
+    future: Pin<Box<dyn Future<Output = ()> + Send>>,
+    abort: AbortHandle
 }
 
 impl Task {
-    pub fn new(future: impl Future<Output =()> + 'static) -> Task {
+    fn with_options(name: Option<String>, priority: Priority, future: impl Future<Output =()> + Send + 'static) -> Task {
         Task {
             id: TaskId::new(),
-            future: Box::pin(future)
+            name,
+            priority,
+            future: Box::pin(future),
+            abort: AbortHandle::new()
         }
     }
 
     fn poll(&mut self, context: &mut Context) -> Poll<()> {
+        self.abort.register(context.waker());
         self.future.as_mut().poll(context)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,208 @@
+use conquer_once::spin::OnceCell;
+use crossbeam_queue::ArrayQueue;
+use crate::input::{self, MouseButtons, MouseEvent};
+use crate::ps2;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::task::{Poll, Context};
+use futures_util::stream::{Stream, StreamExt};
+use futures_util::task::AtomicWaker;
+use x86_64::instructions::interrupts;
+use x86_64::instructions::port::Port;
+
+static WAKER: AtomicWaker = AtomicWaker::new();
+static DROPPED_BYTES: AtomicU64 = AtomicU64::new(0);
+static HAS_WHEEL: AtomicBool = AtomicBool::new(false);
+pub static MOUSE_QUEUE: OnceCell<ArrayQueue<u8>> = OnceCell::uninit();
+
+pub const MOUSE_QUEUE_SIZE: usize = 100;
+
+const SET_DEFAULTS: u8 = 0xF6;
+const ENABLE_REPORTING: u8 = 0xF4;
+const SET_SAMPLE_RATE: u8 = 0xF3;
+const GET_ID: u8 = 0xF2;
+
+// Reported by mice that answered the IntelliMouse knock and send 4 byte packets.
+const INTELLIMOUSE_ID: u8 = 3;
+
+// Mask registers of both PICs, IRQ12 comes in through the cascade on IRQ2.
+const PIC_1_DATA: u16 = 0x21;
+const PIC_2_DATA: u16 = 0xA1;
+const CASCADE_LINE: u8 = 2;
+const MOUSE_LINE: u8 = 12 - 8;
+
+const CONFIG_IRQ12: u8 = 0b10;
+const CONFIG_AUX_CLOCK_DISABLED: u8 = 0b10_0000;
+
+// First packet byte: buttons, a bit that is always set and the sign and overflow bits.
+const ALWAYS_SET: u8 = 0b1000;
+const X_SIGN: u8 = 0b1_0000;
+const Y_SIGN: u8 = 0b10_0000;
+const OVERFLOW: u8 = 0b1100_0000;
+
+pub fn dropped_bytes() -> u64 {
+    DROPPED_BYTES.load(Ordering::Relaxed)
+}
+
+pub fn has_wheel() -> bool {
+    HAS_WHEEL.load(Ordering::Relaxed)
+}
+
+// Enables the mouse behind the 8042 and tries to switch it into IntelliMouse mode.
+// Returns false if there is no mouse. IRQ12 is only turned on at the end, so the
+// answers can be polled here.
+pub fn init() -> bool {
+    interrupts::without_interrupts(|| {
+        ps2::enable_aux();
+
+        let config = match ps2::read_config() {
+            Some(config) => config,
+            None => return false
+        };
+
+        ps2::write_config(config & !(CONFIG_IRQ12 | CONFIG_AUX_CLOCK_DISABLED));
+
+        if !ps2::write_aux(SET_DEFAULTS) {
+            return false;
+        }
+
+        // Setting the sample rate to 200, 100 and 80 in a row unlocks the wheel.
+        for rate in [200, 100, 80] {
+            ps2::write_aux(SET_SAMPLE_RATE);
+            ps2::write_aux(rate);
+        }
+
+        if ps2::write_aux(GET_ID) {
+            HAS_WHEEL.store(ps2::read_data() == Some(INTELLIMOUSE_ID), Ordering::Relaxed);
+        }
+
+        if !ps2::write_aux(ENABLE_REPORTING) {
+            return false;
+        }
+
+        ps2::write_config((config & !CONFIG_AUX_CLOCK_DISABLED) | CONFIG_IRQ12);
+        unmask(PIC_1_DATA, CASCADE_LINE);
+        unmask(PIC_2_DATA, MOUSE_LINE);
+
+        true
+    })
+}
+
+fn unmask(port: u16, line: u8) {
+    let mut port: Port<u8> = Port::new(port);
+
+    unsafe {
+        let mask = port.read();
+        port.write(mask & !(1 << line));
+    }
+}
+
+// Like the keyboard, dropped bytes are only counted from the interrupt handler.
+pub fn add_byte(byte: u8) {
+    if let Ok(queue) = MOUSE_QUEUE.try_get() {
+        if let Err(_) = queue.push(byte) {
+            DROPPED_BYTES.fetch_add(1, Ordering::Relaxed);
+        } else {
+            WAKER.wake();
+        }
+    }
+}
+
+// Puts packets back together and publishes them as mouse events in the input module.
+pub async fn handle_packets() {
+    let mut bytes = MouseStream::new();
+    let mut packet = [0u8; 4];
+    let mut received = 0;
+
+    while let Some(byte) = bytes.next().await {
+        // A first byte without the always set bit means a byte got lost, so wait for
+        // something that looks like the start of a packet again.
+        if received == 0 && byte & ALWAYS_SET == 0 {
+            continue;
+        }
+
+        packet[received] = byte;
+        received += 1;
+
+        let length = if has_wheel() { 4 } else { 3 };
+
+        if received < length {
+            continue;
+        }
+
+        received = 0;
+
+        if packet[0] & OVERFLOW != 0 {
+            continue;
+        }
+
+        input::publish_mouse(decode(&packet, length));
+    }
+}
+
+fn decode(packet: &[u8; 4], length: usize) -> MouseEvent {
+    let flags = packet[0];
+
+    let mut dx = packet[1] as i16;
+    let mut dy = packet[2] as i16;
+
+    if flags & X_SIGN != 0 {
+        dx -= 0x100;
+    }
+
+    if flags & Y_SIGN != 0 {
+        dy -= 0x100;
+    }
+
+    // Only the low 4 bits carry the wheel movement.
+    let wheel = if length == 4 {
+        ((packet[3] << 4) as i8) >> 4
+    } else {
+        0
+    };
+
+    MouseEvent {
+        dx,
+        // The mouse counts upwards as positive.
+        dy: -dy,
+        wheel,
+        buttons: MouseButtons {
+            left: flags & 0b1 != 0,
+            right: flags & 0b10 != 0,
+            middle: flags & 0b100 != 0
+        }
+    }
+}
+
+pub struct MouseStream {
+    _private: ()
+}
+
+impl MouseStream {
+    pub fn new() -> Self {
+        MouseStream {
+            _private: ()
+        }
+    }
+}
+
+impl Stream for MouseStream {
+    type Item = u8;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<u8>> {
+        let queue = MOUSE_QUEUE.try_get().expect("Not initialized");
+
+        if let Ok(byte) = queue.pop() {
+            return Poll::Ready(Some(byte));
+        }
+
+        WAKER.register(&cx.waker());
+        match queue.pop() {
+            Ok(byte) => {
+                WAKER.take();
+                Poll::Ready(Some(byte))
+            },
+            Err(crossbeam_queue::PopError) => Poll::Pending
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,147 @@
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use lazy_static::lazy_static;
+use spin::Mutex;
+use super::{Priority, TaskId};
+use super::join_handle::AbortHandle;
+
+// How many finished tasks are kept around so they still show up in listings.
+const FINISHED_HISTORY: usize = 16;
+
+lazy_static! {
+    static ref REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+        tasks: BTreeMap::new(),
+        running: None,
+        finished: VecDeque::new()
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    Running,
+    Queued,
+    Pending,
+    Finished,
+    Cancelled
+}
+
+impl TaskState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskState::Running => "running",
+            TaskState::Queued => "queued",
+            TaskState::Pending => "pending",
+            TaskState::Finished => "finished",
+            TaskState::Cancelled => "cancelled"
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskSnapshot {
+    pub id: u64,
+    pub name: Option<String>,
+    pub priority: Priority,
+    pub state: TaskState,
+    pub polls: u64,
+    pub poll_cycles: u64
+}
+
+struct TaskRecord {
+    name: Option<String>,
+    priority: Priority,
+    polls: u64,
+    poll_cycles: u64,
+    queued: Arc<AtomicBool>,
+    abort: AbortHandle,
+    end_state: Option<TaskState>
+}
+
+struct Registry {
+    tasks: BTreeMap<TaskId, TaskRecord>,
+    running: Option<TaskId>,
+    finished: VecDeque<TaskId>
+}
+
+pub(super) fn register(task_id: TaskId, name: Option<String>, priority: Priority, queued: Arc<AtomicBool>, abort: AbortHandle) {
+    REGISTRY.lock().tasks.insert(task_id, TaskRecord {
+        name,
+        priority,
+        polls: 0,
+        poll_cycles: 0,
+        queued,
+        abort,
+        end_state: None
+    });
+}
+
+pub(super) fn start_poll(task_id: TaskId) {
+    REGISTRY.lock().running = Some(task_id);
+}
+
+pub(super) fn end_poll(task_id: TaskId, cycles: u64) {
+    let mut registry = REGISTRY.lock();
+    registry.running = None;
+
+    if let Some(record) = registry.tasks.get_mut(&task_id) {
+        record.polls += 1;
+        record.poll_cycles += cycles;
+    }
+}
+
+pub(super) fn finish(task_id: TaskId, state: TaskState) {
+    let mut registry = REGISTRY.lock();
+
+    if let Some(record) = registry.tasks.get_mut(&task_id) {
+        record.end_state = Some(state);
+    }
+
+    registry.finished.push_back(task_id);
+
+    if FINISHED_HISTORY < registry.finished.len() {
+        if let Some(oldest) = registry.finished.pop_front() {
+            registry.tasks.remove(&oldest);
+        }
+    }
+}
+
+pub fn snapshot() -> Vec<TaskSnapshot> {
+    let registry = REGISTRY.lock();
+
+    registry.tasks.iter().map(|(task_id, record)| {
+        let state = if let Some(state) = record.end_state {
+            state
+        } else if registry.running == Some(*task_id) {
+            TaskState::Running
+        } else if record.queued.load(Ordering::Acquire) {
+            TaskState::Queued
+        } else {
+            TaskState::Pending
+        };
+
+        TaskSnapshot {
+            id: task_id.0,
+            name: record.name.clone(),
+            priority: record.priority,
+            state,
+            polls: record.polls,
+            poll_cycles: record.poll_cycles
+        }
+    }).collect()
+}
+
+// Returns false if there is no live task with this ID.
+pub fn abort(id: u64) -> bool {
+    let registry = REGISTRY.lock();
+
+    match registry.tasks.get(&TaskId(id)) {
+        Some(record) if record.end_state.is_none() => {
+            record.abort.abort();
+            true
+        },
+        _ => false
+    }
+}
\ No newline at end of file
@@ -0,0 +1,21 @@
+pub mod mpsc;
+
+// Spin lock that keeps interrupts disabled while held, so channels can be
+// used from interrupt handlers without deadlocking against a task.
+struct IrqLock<T> {
+    inner: spin::Mutex<T>
+}
+
+impl<T> IrqLock<T> {
+    const fn new(value: T) -> IrqLock<T> {
+        IrqLock {
+            inner: spin::Mutex::new(value)
+        }
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            f(&mut self.inner.lock())
+        })
+    }
+}
\ No newline at end of file
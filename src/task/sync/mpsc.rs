@@ -0,0 +1,113 @@
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use futures_util::stream::Stream;
+use super::IrqLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrySendError<T> {
+    Full(T),
+    Closed(T)
+}
+
+struct Channel<T> {
+    queue: VecDeque<T>,
+    capacity: usize,
+    senders: usize,
+    receiver_alive: bool,
+    receiver_waker: Option<Waker>
+}
+
+impl<T> Channel<T> {
+    fn wake_receiver(&mut self) {
+        if let Some(waker) = self.receiver_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+// Bounded channel. Sending never waits, a full channel turns the message away, so it
+// can be used from interrupt handlers.
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "Channel capacity must be positive.");
+
+    let channel = Arc::new(IrqLock::new(Channel {
+        queue: VecDeque::new(),
+        capacity,
+        senders: 1,
+        receiver_alive: true,
+        receiver_waker: None
+    }));
+
+    (Sender { channel: channel.clone() }, Receiver { channel })
+}
+
+pub struct Sender<T> {
+    channel: Arc<IrqLock<Channel<T>>>
+}
+
+impl<T> Sender<T> {
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.channel.with(|channel| {
+            if !channel.receiver_alive {
+                Err(TrySendError::Closed(value))
+            } else if channel.capacity <= channel.queue.len() {
+                Err(TrySendError::Full(value))
+            } else {
+                channel.queue.push_back(value);
+                channel.wake_receiver();
+                Ok(())
+            }
+        })
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        self.channel.with(|channel| channel.senders += 1);
+
+        Sender {
+            channel: self.channel.clone()
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.channel.with(|channel| {
+            channel.senders -= 1;
+
+            if channel.senders == 0 {
+                channel.wake_receiver();
+            }
+        });
+    }
+}
+
+pub struct Receiver<T> {
+    channel: Arc<IrqLock<Channel<T>>>
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.channel.with(|channel| channel.receiver_alive = false);
+    }
+}
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.channel.with(|channel| {
+            match channel.queue.pop_front() {
+                Some(value) => Poll::Ready(Some(value)),
+                None if channel.senders == 0 => Poll::Ready(None),
+                None => {
+                    channel.receiver_waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+    }
+}
\ No newline at end of file
@@ -0,0 +1,79 @@
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+// The PIT runs at its power-on rate, nothing reprograms it: 1193182 Hz divided by 65536,
+// about 18.2 ticks a second.
+const PIT_FREQUENCY: u64 = 1_193_182;
+const PIT_DIVISOR: u64 = 65_536;
+
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+// Tasks waiting for a tick, with the tick they wait for. Also taken by the timer
+// interrupt, so only ever locked with interrupts disabled.
+static SLEEPERS: Mutex<Vec<(u64, Waker)>> = Mutex::new(Vec::new());
+
+// Called by the timer interrupt.
+pub(crate) fn tick() {
+    let now = TICKS.fetch_add(1, Ordering::Relaxed) + 1;
+
+    let mut sleepers = SLEEPERS.lock();
+    let mut index = 0;
+
+    while index < sleepers.len() {
+        if sleepers[index].0 <= now {
+            sleepers.swap_remove(index).1.wake();
+        } else {
+            index += 1;
+        }
+    }
+}
+
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+pub fn uptime() -> Duration {
+    Duration::from_millis(ticks() * PIT_DIVISOR * 1000 / PIT_FREQUENCY)
+}
+
+// Resolves once at least the duration has passed, rounded up to whole ticks.
+pub fn sleep(duration: Duration) -> Sleep {
+    let ticks = (duration.as_millis() as u64 * PIT_FREQUENCY + PIT_DIVISOR * 1000 - 1) / (PIT_DIVISOR * 1000);
+
+    Sleep {
+        deadline: self::ticks() + ticks.max(1)
+    }
+}
+
+pub struct Sleep {
+    deadline: u64
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let deadline = self.deadline;
+
+        without_interrupts(|| {
+            if deadline <= ticks() {
+                return Poll::Ready(());
+            }
+
+            let mut sleepers = SLEEPERS.lock();
+
+            match sleepers.iter_mut().find(|(_, waker)| waker.will_wake(cx.waker())) {
+                Some(sleeper) => sleeper.0 = sleeper.0.min(deadline),
+                None => sleepers.push((deadline, cx.waker().clone()))
+            }
+
+            Poll::Pending
+        })
+    }
+}
\ No newline at end of file
@@ -0,0 +1,297 @@
+use alloc::vec::Vec;
+use spin::Mutex;
+use x86_64::instructions::port::Port;
+use crate::psf::Font;
+use crate::vga_buffer;
+
+// Switches between VGA text modes by programming the registers directly, as there is no
+// BIOS to ask once the kernel runs. The register values are the standard ones for
+// each mode.
+
+const MISC_WRITE: u16 = 0x3C2;
+const SEQUENCER_INDEX: u16 = 0x3C4;
+const SEQUENCER_DATA: u16 = 0x3C5;
+const GRAPHICS_INDEX: u16 = 0x3CE;
+const GRAPHICS_DATA: u16 = 0x3CF;
+const CRTC_INDEX: u16 = 0x3D4;
+const CRTC_DATA: u16 = 0x3D5;
+const ATTRIBUTE_WRITE: u16 = 0x3C0;
+const ATTRIBUTE_READ: u16 = 0x3C1;
+// Reading it resets the attribute controller's index/data flip-flop.
+const INPUT_STATUS: u16 = 0x3DA;
+
+const SEQUENCER_MAP_MASK: u8 = 0x02;
+const SEQUENCER_MEMORY_MODE: u8 = 0x04;
+const GRAPHICS_READ_MAP: u8 = 0x04;
+const GRAPHICS_MODE: u8 = 0x05;
+const GRAPHICS_MISC: u8 = 0x06;
+const CRTC_HORIZONTAL_RETRACE_END: u8 = 0x03;
+const CRTC_VERTICAL_RETRACE_END: u8 = 0x11;
+const ATTRIBUTE_MODE_CONTROL: u8 = 0x10;
+// Set after writing the attribute registers, otherwise the screen stays blank.
+const ATTRIBUTE_ENABLE_DISPLAY: u8 = 0x20;
+// In the mode control register. Without it the top attribute bit picks a bright
+// background instead of blinking text.
+const BLINK: u8 = 0x08;
+
+// The font lives in plane 2, each glyph in a 32 byte slot whatever its height.
+const FONT_PLANE: u8 = 2;
+const GLYPH_SLOT: usize = 32;
+const GLYPH_COUNT: usize = 256;
+const ORIGINAL_CHARACTER_HEIGHT: usize = 16;
+
+const GRAPHICS: [u8; 9] = [0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x0E, 0x00, 0xFF];
+
+// The palette, mode control, overscan, plane enable and panning. 9 dot wide characters
+// need one pixel of panning, 8 dot wide ones none. Blinking is off, for all 16
+// background colours.
+const ATTRIBUTE_9_DOT: [u8; 21] = [
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x14, 0x07, 0x38, 0x39, 0x3A, 0x3B, 0x3C, 0x3D, 0x3E, 0x3F,
+    0x04, 0x00, 0x0F, 0x08, 0x00
+];
+const ATTRIBUTE_8_DOT: [u8; 21] = [
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x14, 0x07, 0x38, 0x39, 0x3A, 0x3B, 0x3C, 0x3D, 0x3E, 0x3F,
+    0x04, 0x00, 0x0F, 0x00, 0x00
+];
+
+pub struct Mode {
+    pub name: &'static str,
+    pub width: usize,
+    pub height: usize,
+    pub character_height: u8,
+    misc: u8,
+    sequencer: [u8; 5],
+    crtc: [u8; 25],
+    attribute: &'static [u8; 21]
+}
+
+pub static MODES: [Mode; 4] = [
+    Mode {
+        name: "80x25",
+        width: 80,
+        height: 25,
+        character_height: 16,
+        misc: 0x67,
+        sequencer: [0x03, 0x00, 0x03, 0x00, 0x02],
+        crtc: [
+            0x5F, 0x4F, 0x50, 0x82, 0x55, 0x81, 0xBF, 0x1F, 0x00, 0x4F, 0x0D, 0x0E, 0x00, 0x00, 0x00, 0x50,
+            0x9C, 0x0E, 0x8F, 0x28, 0x1F, 0x96, 0xB9, 0xA3, 0xFF
+        ],
+        attribute: &ATTRIBUTE_9_DOT
+    },
+    Mode {
+        name: "80x50",
+        width: 80,
+        height: 50,
+        character_height: 8,
+        misc: 0x67,
+        sequencer: [0x03, 0x00, 0x03, 0x00, 0x02],
+        crtc: [
+            0x5F, 0x4F, 0x50, 0x82, 0x55, 0x81, 0xBF, 0x1F, 0x00, 0x47, 0x06, 0x07, 0x00, 0x00, 0x01, 0x40,
+            0x9C, 0x8E, 0x8F, 0x28, 0x1F, 0x96, 0xB9, 0xA3, 0xFF
+        ],
+        attribute: &ATTRIBUTE_9_DOT
+    },
+    Mode {
+        name: "90x30",
+        width: 90,
+        height: 30,
+        character_height: 16,
+        misc: 0xE7,
+        sequencer: [0x03, 0x01, 0x03, 0x00, 0x02],
+        crtc: [
+            0x6B, 0x59, 0x5A, 0x82, 0x60, 0x8D, 0x0B, 0x3E, 0x00, 0x4F, 0x0D, 0x0E, 0x00, 0x00, 0x00, 0x00,
+            0xEA, 0x0C, 0xDF, 0x2D, 0x10, 0xE8, 0x05, 0xA3, 0xFF
+        ],
+        attribute: &ATTRIBUTE_8_DOT
+    },
+    Mode {
+        name: "90x60",
+        width: 90,
+        height: 60,
+        character_height: 8,
+        misc: 0xE7,
+        sequencer: [0x03, 0x01, 0x03, 0x00, 0x02],
+        crtc: [
+            0x6B, 0x59, 0x5A, 0x82, 0x60, 0x8D, 0x0B, 0x3E, 0x00, 0x47, 0x06, 0x07, 0x00, 0x00, 0x00, 0x00,
+            0xEA, 0x0C, 0xDF, 0x2D, 0x08, 0xE8, 0x05, 0xA3, 0xFF
+        ],
+        attribute: &ATTRIBUTE_8_DOT
+    }
+];
+
+// The 8x16 font the BIOS loaded, saved before the first 8x8 font overwrites it.
+static ORIGINAL_FONT: Mutex<Option<Vec<u8>>> = Mutex::new(None);
+
+static CURRENT: Mutex<&'static Mode> = Mutex::new(&MODES[0]);
+
+pub fn find(name: &str) -> Option<&'static Mode> {
+    MODES.iter().find(|mode| mode.name == name)
+}
+
+pub fn current() -> &'static Mode {
+    *CURRENT.lock()
+}
+
+// Programs the registers and loads a font of the right height. Only for use with
+// interrupts disabled, the console has to be resized right after.
+pub fn set(mode: &'static Mode) {
+    let mut original = ORIGINAL_FONT.lock();
+    let original = original.get_or_insert_with(read_original_font);
+
+    write_registers(mode);
+
+    if mode.character_height as usize == ORIGINAL_CHARACTER_HEIGHT {
+        write_font(ORIGINAL_CHARACTER_HEIGHT, |glyph, row| original[glyph * ORIGINAL_CHARACTER_HEIGHT + row]);
+    } else {
+        let font = Font::default();
+
+        write_font(mode.character_height as usize, |glyph, row| small_glyph_row(&font, original, glyph, row));
+    }
+
+    *CURRENT.lock() = mode;
+}
+
+// For the mode the BIOS left, the others have blinking off already.
+pub fn disable_blink() {
+    let mut status: Port<u8> = Port::new(INPUT_STATUS);
+    let mut attribute: Port<u8> = Port::new(ATTRIBUTE_WRITE);
+    let mut attribute_read: Port<u8> = Port::new(ATTRIBUTE_READ);
+
+    unsafe {
+        status.read();
+        attribute.write(ATTRIBUTE_MODE_CONTROL | ATTRIBUTE_ENABLE_DISPLAY);
+
+        let mode_control = attribute_read.read();
+        attribute.write(mode_control & !BLINK);
+
+        status.read();
+    }
+}
+
+// The built in 8x8 font where it has a glyph. The others are the original ones with
+// every two scanlines merged into one.
+fn small_glyph_row(font: &Font, original: &[u8], glyph: usize, row: usize) -> u8 {
+    let has_glyph = glyph == b' ' as usize || (0..font.height).any(|y| (0..font.width).any(|x| font.is_set(glyph as u8, x, y)));
+
+    if has_glyph && font.height == 8 {
+        return (0..8).fold(0, |bits, x| if font.is_set(glyph as u8, x, row) { bits | 0x80 >> x } else { bits });
+    }
+
+    let first = glyph * ORIGINAL_CHARACTER_HEIGHT + row * 2;
+
+    original[first] | original[first + 1]
+}
+
+fn write_registers(mode: &Mode) {
+    let mut misc: Port<u8> = Port::new(MISC_WRITE);
+    unsafe { misc.write(mode.misc) };
+
+    for (register, value) in mode.sequencer.iter().enumerate() {
+        write_indexed(SEQUENCER_INDEX, SEQUENCER_DATA, register as u8, *value);
+    }
+
+    // The timing registers are write protected, lift that and keep it lifted.
+    write_indexed(CRTC_INDEX, CRTC_DATA, CRTC_HORIZONTAL_RETRACE_END, read_indexed(CRTC_INDEX, CRTC_DATA, CRTC_HORIZONTAL_RETRACE_END) | 0x80);
+    write_indexed(CRTC_INDEX, CRTC_DATA, CRTC_VERTICAL_RETRACE_END, read_indexed(CRTC_INDEX, CRTC_DATA, CRTC_VERTICAL_RETRACE_END) & !0x80);
+
+    for (register, value) in mode.crtc.iter().enumerate() {
+        let value = match register as u8 {
+            CRTC_HORIZONTAL_RETRACE_END => value | 0x80,
+            CRTC_VERTICAL_RETRACE_END => value & !0x80,
+            _ => *value
+        };
+
+        write_indexed(CRTC_INDEX, CRTC_DATA, register as u8, value);
+    }
+
+    for (register, value) in GRAPHICS.iter().enumerate() {
+        write_indexed(GRAPHICS_INDEX, GRAPHICS_DATA, register as u8, *value);
+    }
+
+    let mut status: Port<u8> = Port::new(INPUT_STATUS);
+    let mut attribute: Port<u8> = Port::new(ATTRIBUTE_WRITE);
+
+    for (register, value) in mode.attribute.iter().enumerate() {
+        unsafe {
+            status.read();
+            attribute.write(register as u8);
+            attribute.write(*value);
+        }
+    }
+
+    unsafe {
+        status.read();
+        attribute.write(ATTRIBUTE_ENABLE_DISPLAY);
+    }
+}
+
+fn read_original_font() -> Vec<u8> {
+    let mut font = Vec::with_capacity(GLYPH_COUNT * ORIGINAL_CHARACTER_HEIGHT);
+
+    with_font_plane(|plane| {
+        for glyph in 0..GLYPH_COUNT {
+            for row in 0..ORIGINAL_CHARACTER_HEIGHT {
+                font.push(unsafe { plane.add(glyph * GLYPH_SLOT + row).read_volatile() });
+            }
+        }
+    });
+
+    font
+}
+
+fn write_font(height: usize, row_of: impl Fn(usize, usize) -> u8) {
+    with_font_plane(|plane| {
+        for glyph in 0..GLYPH_COUNT {
+            for row in 0..GLYPH_SLOT {
+                let bits = if row < height { row_of(glyph, row) } else { 0 };
+
+                unsafe { plane.add(glyph * GLYPH_SLOT + row).write_volatile(bits) };
+            }
+        }
+    });
+}
+
+// Maps plane 2 alone to where text memory is, with plain instead of odd/even addressing, and
+// restores text mode access afterwards.
+fn with_font_plane(f: impl FnOnce(*mut u8)) {
+    let map_mask = read_indexed(SEQUENCER_INDEX, SEQUENCER_DATA, SEQUENCER_MAP_MASK);
+    let memory_mode = read_indexed(SEQUENCER_INDEX, SEQUENCER_DATA, SEQUENCER_MEMORY_MODE);
+    let read_map = read_indexed(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_READ_MAP);
+    let graphics_mode = read_indexed(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_MODE);
+    let graphics_misc = read_indexed(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_MISC);
+
+    write_indexed(SEQUENCER_INDEX, SEQUENCER_DATA, SEQUENCER_MAP_MASK, 1 << FONT_PLANE);
+    write_indexed(SEQUENCER_INDEX, SEQUENCER_DATA, SEQUENCER_MEMORY_MODE, memory_mode | 0x04);
+    write_indexed(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_READ_MAP, FONT_PLANE);
+    write_indexed(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_MODE, graphics_mode & !0x10);
+    write_indexed(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_MISC, graphics_misc & !0x02);
+
+    f(vga_buffer::text_memory());
+
+    write_indexed(SEQUENCER_INDEX, SEQUENCER_DATA, SEQUENCER_MAP_MASK, map_mask);
+    write_indexed(SEQUENCER_INDEX, SEQUENCER_DATA, SEQUENCER_MEMORY_MODE, memory_mode);
+    write_indexed(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_READ_MAP, read_map);
+    write_indexed(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_MODE, graphics_mode);
+    write_indexed(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_MISC, graphics_misc);
+}
+
+fn write_indexed(index_port: u16, data_port: u16, register: u8, value: u8) {
+    let mut index: Port<u8> = Port::new(index_port);
+    let mut data: Port<u8> = Port::new(data_port);
+
+    unsafe {
+        index.write(register);
+        data.write(value);
+    }
+}
+
+fn read_indexed(index_port: u16, data_port: u16, register: u8) -> u8 {
+    let mut index: Port<u8> = Port::new(index_port);
+    let mut data: Port<u8> = Port::new(data_port);
+
+    unsafe {
+        index.write(register);
+        data.read()
+    }
+}
\ No newline at end of file
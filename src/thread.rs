@@ -0,0 +1,427 @@
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::arch::{asm, global_asm};
+use core::future::Future;
+use core::task::{Context, Poll, Waker};
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+use x86_64::registers::segmentation::{CS, SS, Segment};
+
+pub const YIELD_INTERRUPT: u8 = 0x81;
+
+const STACK_SIZE: usize = 4096 * 8;
+
+// Written at the bottom of every stack. Stacks live on the heap without a guard page, so
+// a thread that runs past the end is only caught by this getting overwritten.
+const STACK_CANARY: u64 = 0x57AC_CA4A_57AC_CA4A;
+
+// Both entry points save every general purpose register on the interrupted thread's
+// stack, let the scheduler pick the stack pointer of the thread to resume and restore
+// its registers from there. The CPU keeps interrupt frames 16 byte aligned, so the stack
+// is aligned again after the 15 pushes.
+macro_rules! switch_entry {
+    ($entry:literal, $handler:literal) => {
+        global_asm!(concat!(
+            ".global ", $entry, "\n",
+            $entry, ":\n",
+            "    push rax\n",
+            "    push rbx\n",
+            "    push rcx\n",
+            "    push rdx\n",
+            "    push rsi\n",
+            "    push rdi\n",
+            "    push rbp\n",
+            "    push r8\n",
+            "    push r9\n",
+            "    push r10\n",
+            "    push r11\n",
+            "    push r12\n",
+            "    push r13\n",
+            "    push r14\n",
+            "    push r15\n",
+            "    mov rdi, rsp\n",
+            "    call ", $handler, "\n",
+            "    mov rsp, rax\n",
+            "    pop r15\n",
+            "    pop r14\n",
+            "    pop r13\n",
+            "    pop r12\n",
+            "    pop r11\n",
+            "    pop r10\n",
+            "    pop r9\n",
+            "    pop r8\n",
+            "    pop rbp\n",
+            "    pop rdi\n",
+            "    pop rsi\n",
+            "    pop rdx\n",
+            "    pop rcx\n",
+            "    pop rbx\n",
+            "    pop rax\n",
+            "    iretq\n"
+        ));
+    };
+}
+
+switch_entry!("timer_interrupt_entry", "timer_interrupt_switch");
+switch_entry!("yield_interrupt_entry", "yield_interrupt_switch");
+
+extern "C" {
+    pub fn timer_interrupt_entry();
+    pub fn yield_interrupt_entry();
+}
+
+#[no_mangle]
+extern "C" fn timer_interrupt_switch(rsp: u64) -> u64 {
+    crate::interrupts::end_of_timer_interrupt();
+    crate::task::timer::tick();
+
+    switch(rsp)
+}
+
+#[no_mangle]
+extern "C" fn yield_interrupt_switch(rsp: u64) -> u64 {
+    switch(rsp)
+}
+
+fn switch(rsp: u64) -> u64 {
+    // Checked before switching, with the lock released again, as printing the panic
+    // needs it.
+    let overflowed = SCHEDULER.lock().as_mut().and_then(|scheduler| {
+        let thread = scheduler.current_thread();
+
+        if thread.stack_intact() { None } else { Some(thread.name) }
+    });
+
+    if let Some(name) = overflowed {
+        panic!("Thread {} overflowed its stack.", name);
+    }
+
+    // Threads only take the lock with interrupts disabled, so it can't be held here.
+    match SCHEDULER.lock().as_mut() {
+        Some(scheduler) => scheduler.switch(rsp),
+        None => rsp
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ThreadId(u64);
+
+impl ThreadId {
+    fn new() -> ThreadId {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+        ThreadId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadState {
+    Running,
+    Ready,
+    Parked,
+    Finished
+}
+
+impl ThreadState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ThreadState::Running => "running",
+            ThreadState::Ready => "ready",
+            ThreadState::Parked => "parked",
+            ThreadState::Finished => "finished"
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ThreadSnapshot {
+    pub id: u64,
+    pub name: &'static str,
+    pub state: ThreadState
+}
+
+struct Thread {
+    name: &'static str,
+    state: ThreadState,
+    rsp: u64,
+    unpark_token: bool,
+    // Freed with the thread. None for the boot thread, which keeps running on the
+    // bootloader's stack.
+    stack: Option<Box<[u8]>>
+}
+
+impl Thread {
+    fn stack_intact(&self) -> bool {
+        match &self.stack {
+            Some(stack) => unsafe { (stack.as_ptr() as *const u64).read_unaligned() == STACK_CANARY },
+            None => true
+        }
+    }
+}
+
+struct Scheduler {
+    threads: BTreeMap<ThreadId, Thread>,
+    run_queue: VecDeque<ThreadId>,
+    current: ThreadId
+}
+
+static SCHEDULER: Mutex<Option<Scheduler>> = Mutex::new(None);
+
+impl Scheduler {
+    // Runs inside the interrupt handlers, so it must not allocate. The run queue
+    // holds every thread at most once and has room for all of them.
+    fn switch(&mut self, rsp: u64) -> u64 {
+        let current = self.current;
+
+        if let Some(thread) = self.threads.get_mut(&current) {
+            thread.rsp = rsp;
+
+            if thread.state == ThreadState::Running {
+                thread.state = ThreadState::Ready;
+                self.run_queue.push_back(current);
+            }
+        }
+
+        while let Some(next) = self.run_queue.pop_front() {
+            if let Some(thread) = self.threads.get_mut(&next) {
+                if thread.state == ThreadState::Ready {
+                    thread.state = ThreadState::Running;
+                    self.current = next;
+
+                    return thread.rsp;
+                }
+            }
+        }
+
+        // The idle thread is always ready, so this only happens before it exists.
+        rsp
+    }
+
+    fn current_thread(&mut self) -> &mut Thread {
+        self.threads.get_mut(&self.current).expect("Current thread not registered.")
+    }
+
+    fn reap(&mut self) -> Vec<Thread> {
+        let current = self.current;
+        let finished: Vec<ThreadId> = self.threads.iter()
+            .filter(|(id, thread)| **id != current && thread.state == ThreadState::Finished)
+            .map(|(id, _)| *id)
+            .collect();
+
+        finished.iter().filter_map(|id| self.threads.remove(id)).collect()
+    }
+}
+
+// Turns the code running right now into the boot thread and starts the idle thread.
+pub fn init() {
+    let boot_thread = ThreadId::new();
+
+    let mut threads = BTreeMap::new();
+    threads.insert(boot_thread, Thread {
+        name: "boot",
+        state: ThreadState::Running,
+        rsp: 0,
+        unpark_token: false,
+        stack: None
+    });
+
+    let scheduler = Scheduler {
+        threads,
+        run_queue: VecDeque::with_capacity(1),
+        current: boot_thread
+    };
+
+    interrupts::without_interrupts(|| {
+        *SCHEDULER.lock() = Some(scheduler);
+    });
+
+    // Sleeps until the next interrupt, then lets whoever it woke run right away, like
+    // the executor yielding while it had nothing to poll.
+    spawn("idle", || loop {
+        x86_64::instructions::hlt();
+        yield_now();
+    });
+}
+
+pub fn spawn<F>(name: &'static str, entry: F) -> ThreadId
+where
+    F: FnOnce() + Send + 'static
+{
+    let entry: Box<Box<dyn FnOnce() + Send>> = Box::new(Box::new(entry));
+    let mut stack = vec![0u8; STACK_SIZE].into_boxed_slice();
+    let rsp = prepare_stack(&mut stack, Box::into_raw(entry) as u64);
+
+    let id = ThreadId::new();
+
+    // Finished threads are handed out of the critical section so their stacks get
+    // freed with interrupts enabled again.
+    let _finished = interrupts::without_interrupts(|| {
+        let mut guard = SCHEDULER.lock();
+        let scheduler = guard.as_mut().expect("Scheduler not initialized.");
+
+        let finished = scheduler.reap();
+
+        scheduler.threads.insert(id, Thread {
+            name,
+            state: ThreadState::Ready,
+            rsp,
+            unpark_token: false,
+            stack: Some(stack)
+        });
+
+        let threads = scheduler.threads.len();
+        scheduler.run_queue.reserve(threads);
+        scheduler.run_queue.push_back(id);
+
+        finished
+    });
+
+    id
+}
+
+pub fn snapshot() -> Vec<ThreadSnapshot> {
+    interrupts::without_interrupts(|| {
+        match SCHEDULER.lock().as_ref() {
+            Some(scheduler) => scheduler.threads.iter().map(|(id, thread)| ThreadSnapshot {
+                id: id.0,
+                name: thread.name,
+                state: thread.state
+            }).collect(),
+            None => Vec::new()
+        }
+    })
+}
+
+// None before the scheduler is initialized.
+pub fn current() -> Option<ThreadId> {
+    interrupts::without_interrupts(|| {
+        SCHEDULER.lock().as_ref().map(|scheduler| scheduler.current)
+    })
+}
+
+pub fn yield_now() {
+    unsafe {
+        asm!("int {}", const YIELD_INTERRUPT);
+    }
+}
+
+// Blocks the current thread until unpark is called for it. Returns immediately
+// if it was unparked in the meantime.
+pub fn park() {
+    let parked = interrupts::without_interrupts(|| {
+        let mut guard = SCHEDULER.lock();
+        let thread = guard.as_mut().expect("Scheduler not initialized.").current_thread();
+
+        if thread.unpark_token {
+            thread.unpark_token = false;
+            false
+        } else {
+            thread.state = ThreadState::Parked;
+            true
+        }
+    });
+
+    if parked {
+        yield_now();
+    }
+}
+
+pub fn unpark(id: ThreadId) {
+    interrupts::without_interrupts(|| {
+        let mut guard = SCHEDULER.lock();
+        let scheduler = guard.as_mut().expect("Scheduler not initialized.");
+
+        if let Some(thread) = scheduler.threads.get_mut(&id) {
+            if thread.state == ThreadState::Parked {
+                thread.state = ThreadState::Ready;
+                scheduler.run_queue.push_back(id);
+            } else {
+                thread.unpark_token = true;
+            }
+        }
+    });
+}
+
+// Runs a future on the current thread, parked whenever the future has to wait. Lets
+// threads use the channels and timers the tasks use.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let id = current().expect("Scheduler not initialized.");
+    let waker = Waker::from(Arc::new(ThreadWaker { id }));
+    let mut context = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+
+        park();
+    }
+}
+
+struct ThreadWaker {
+    id: ThreadId
+}
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        unpark(self.id);
+    }
+}
+
+fn exit() -> ! {
+    interrupts::without_interrupts(|| {
+        let mut guard = SCHEDULER.lock();
+        guard.as_mut().expect("Scheduler not initialized.").current_thread().state = ThreadState::Finished;
+    });
+
+    // A finished thread is never put back into the run queue.
+    loop {
+        yield_now();
+    }
+}
+
+extern "C" fn thread_entry(entry: u64) -> ! {
+    let entry = unsafe { Box::from_raw(entry as *mut Box<dyn FnOnce() + Send>) };
+    entry();
+
+    exit();
+}
+
+// Lays out the stack as if the thread had been interrupted right before entering
+// thread_entry, so the first switch to it "returns" there with entry in rdi. The canary
+// goes at the other end.
+fn prepare_stack(stack: &mut [u8], entry: u64) -> u64 {
+    stack[..8].copy_from_slice(&STACK_CANARY.to_ne_bytes());
+
+    let top = (stack.as_mut_ptr() as u64 + stack.len() as u64) & !0xF;
+    let mut rsp = top;
+
+    let mut push = |value: u64| {
+        rsp -= 8;
+        unsafe { (rsp as *mut u64).write(value) };
+    };
+
+    // Fake return address, thread_entry never returns.
+    push(0);
+    let entry_rsp = top - 8;
+
+    // Interrupt frame consumed by iretq.
+    push(SS::get_reg().0 as u64);
+    push(entry_rsp);
+    push(0x202);
+    push(CS::get_reg().0 as u64);
+    push(thread_entry as extern "C" fn(u64) -> ! as u64);
+
+    // rax, rbx, rcx, rdx, rsi, rdi, rbp, r8 - r15 in the order switch_entry pushes them.
+    for register in 0..15 {
+        push(if register == 5 { entry } else { 0 });
+    }
+
+    rsp
+}
\ No newline at end of file
@@ -1,8 +1,18 @@
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
 use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryInto;
 use core::fmt;
+use core::mem;
+use core::sync::atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering};
 use volatile::Volatile;
-use lazy_static::lazy_static;
-use spin::Mutex;
+use x86_64::instructions::port::Port;
+use x86_64::VirtAddr;
+use crate::ansi::{Action, Csi, Parser};
+use crate::encoding::{self, Utf8Decoder};
+use crate::{console, framebuffer};
 
 #[macro_export]
 macro_rules! print {
@@ -21,7 +31,13 @@ pub fn _print(args: fmt::Arguments) {
     use x86_64::instructions::interrupts;
 
     interrupts::without_interrupts(|| {
-        WRITER.lock().write_fmt(args).unwrap();
+        match console::attached() {
+            Some(index) => console::writer(index).lock().write_fmt(args).unwrap(),
+            None => {
+                console::log(args);
+                console::writer(console::KERNEL_CONSOLE).lock().write_fmt(args).unwrap();
+            }
+        }
     });
 }
 
@@ -47,6 +63,38 @@ pub enum Color {
     White = 15
 }
 
+impl Color {
+    pub const ALL: [Color; 16] = [
+        Color::Black, Color::Blue, Color::Green, Color::Cyan, Color::Red, Color::Magenta, Color::Brown, Color::LightGray,
+        Color::DarkGray, Color::LightBlue, Color::LightGreen, Color::LightCyan, Color::LightRed, Color::Pink, Color::Yellow, Color::White
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Color::Black => "black",
+            Color::Blue => "blue",
+            Color::Green => "green",
+            Color::Cyan => "cyan",
+            Color::Red => "red",
+            Color::Magenta => "magenta",
+            Color::Brown => "brown",
+            Color::LightGray => "lightgray",
+            Color::DarkGray => "darkgray",
+            Color::LightBlue => "lightblue",
+            Color::LightGreen => "lightgreen",
+            Color::LightCyan => "lightcyan",
+            Color::LightRed => "lightred",
+            Color::Pink => "pink",
+            Color::Yellow => "yellow",
+            Color::White => "white"
+        }
+    }
+
+    pub fn from_str(name: &str) -> Option<Color> {
+        Color::ALL.iter().copied().find(|color| color.as_str() == name)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(transparent)]
 pub struct ColorCode(u8);
@@ -55,6 +103,22 @@ impl ColorCode {
     pub fn new(foreground: Color, background: Color) -> ColorCode {
         ColorCode((background as u8) << 4 | (foreground as u8))
     }
+
+    pub fn with_foreground(self, foreground: Color) -> ColorCode {
+        ColorCode(self.0 & 0xF0 | foreground as u8)
+    }
+
+    pub fn foreground(self) -> u8 {
+        self.0 & 0x0F
+    }
+
+    pub fn background(self) -> u8 {
+        self.0 >> 4
+    }
+
+    pub fn inverted(self) -> ColorCode {
+        ColorCode(self.0 << 4 | self.0 >> 4)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -64,109 +128,364 @@ struct ScreenChar {
     color_code: ColorCode
 }
 
-const BUFFER_HEIGHT: usize = 25;
-const BUFFER_WIDTH: usize = 80;
+// Room for the largest text mode. Smaller ones use the top left part.
+pub const MAX_WIDTH: usize = 90;
+pub const MAX_HEIGHT: usize = 60;
+
+// The size of the text mode in use, 80x25 with 16 scanlines per character at boot.
+static WIDTH: AtomicUsize = AtomicUsize::new(80);
+static HEIGHT: AtomicUsize = AtomicUsize::new(25);
+static CHARACTER_HEIGHT: AtomicU8 = AtomicU8::new(16);
+
+// Kept for the status bar at the bottom.
+const STATUS_ROWS: usize = 1;
+
+// Where VGA text memory is mapped. Only its first page is identity mapped, which is
+// enough for 80x25 until the physical memory mapping is known.
+static VGA_ADDRESS: AtomicU64 = AtomicU64::new(0xB8000);
+
+// CRT controller, reached through an index and a data port.
+const CRTC_INDEX: u16 = 0x3D4;
+const CRTC_DATA: u16 = 0x3D5;
+const CURSOR_START: u8 = 0x0A;
+const CURSOR_END: u8 = 0x0B;
+const CURSOR_LOCATION_HIGH: u8 = 0x0E;
+const CURSOR_LOCATION_LOW: u8 = 0x0F;
+
+fn write_crtc(register: u8, value: u8) {
+    let mut index: Port<u8> = Port::new(CRTC_INDEX);
+    let mut data: Port<u8> = Port::new(CRTC_DATA);
+
+    unsafe {
+        index.write(register);
+        data.write(value);
+    }
+}
+
+fn read_crtc(register: u8) -> u8 {
+    let mut index: Port<u8> = Port::new(CRTC_INDEX);
+    let mut data: Port<u8> = Port::new(CRTC_DATA);
+
+    unsafe {
+        index.write(register);
+        data.read()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorStyle {
+    Block,
+    Underline
+}
+
+// Lines kept after they scroll off the top of the screen or get cleared.
+pub const SCROLLBACK_LINES: usize = 5000;
+
+// The most the scrollback of all consoles together takes up on the heap once full: per
+// line the widest cells, what the allocator rounds them up by and the slot in the queue.
+pub const SCROLLBACK_SIZE: usize = console::CONSOLE_COUNT * SCROLLBACK_LINES
+    * (MAX_WIDTH * mem::size_of::<ScreenChar>() + mem::size_of::<usize>() + mem::size_of::<ScrollbackLine>());
+
+type Line = [ScreenChar; MAX_WIDTH];
+
+// A line in the scrollback, stored without the run of identical cells at its end so
+// thousands of mostly empty lines stay small. The colours are kept with every cell.
+struct ScrollbackLine {
+    cells: Box<[ScreenChar]>,
+    fill: ScreenChar
+}
+
+impl ScrollbackLine {
+    fn new(line: &Line) -> ScrollbackLine {
+        let line = &line[..width()];
+        let fill = line[line.len() - 1];
+        let length = line.iter().rposition(|cell| *cell != fill).map_or(0, |index| index + 1);
+
+        ScrollbackLine {
+            cells: line[..length].into(),
+            fill
+        }
+    }
+
+    fn to_line(&self) -> Line {
+        let mut line = [self.fill; MAX_WIDTH];
+        line[..self.cells.len()].copy_from_slice(&self.cells);
+
+        line
+    }
+}
+
+// The screen a full-screen application took over, given back when it leaves.
+struct SavedScreen {
+    screen: Box<[Line; MAX_HEIGHT]>,
+    position: (usize, usize),
+    color_code: ColorCode
+}
+
+// A cell drawn inverted on top of the text, like the mouse pointer or a selection.
+struct Overlay {
+    row: usize,
+    column: usize,
+    original: ScreenChar,
+    drawn: ScreenChar
+}
 
 #[repr(transparent)]
 struct Buffer {
-    chars: [[Volatile<ScreenChar>; BUFFER_WIDTH]; BUFFER_HEIGHT]
+    // Row after row, each as wide as the current mode.
+    chars: [Volatile<ScreenChar>; MAX_WIDTH * MAX_HEIGHT]
+}
+
+fn vga_buffer() -> &'static mut Buffer {
+    unsafe { &mut *(VGA_ADDRESS.load(Ordering::Relaxed) as *mut Buffer) }
+}
+
+pub fn init(physical_memory_offset: VirtAddr) {
+    VGA_ADDRESS.store((physical_memory_offset + 0xB8000u64).as_u64(), Ordering::Relaxed);
+}
+
+// Also where the font plane shows up while it is being loaded.
+pub fn text_memory() -> *mut u8 {
+    VGA_ADDRESS.load(Ordering::Relaxed) as *mut u8
 }
 
-lazy_static! {
-    pub static ref WRITER: Mutex<Writer> = Mutex::new(Writer {
-        column_position: 0,
-        row_position: 0,
-        color_code: ColorCode::new(Color::White, Color::Black),
-        buffer: unsafe { &mut *(0xB8000 as *mut Buffer) }
+pub fn width() -> usize {
+    WIDTH.load(Ordering::Relaxed)
+}
+
+// Rows the writers get, the status bar takes the one below them.
+pub fn height() -> usize {
+    HEIGHT.load(Ordering::Relaxed) - STATUS_ROWS
+}
+
+fn screen_height() -> usize {
+    HEIGHT.load(Ordering::Relaxed)
+}
+
+// Puts a cell on screen, in VGA text memory or drawn on the framebuffer.
+fn show_cell(row: usize, column: usize, character: ScreenChar, cursor: Option<CursorStyle>) {
+    match framebuffer::text_console() {
+        Some(text_console) => {
+            text_console.lock().draw_cell(row,
+                                          column,
+                                          character.ascii_character,
+                                          character.color_code.foreground(),
+                                          character.color_code.background(),
+                                          cursor);
+        },
+        None => vga_buffer().chars[row * width() + column].write(character)
+    }
+}
+
+// Fills the status bar with text, cut off at the edge of the screen.
+pub fn draw_status_line(text: &str) {
+    let color_code = ColorCode::new(Color::Black, Color::LightGray);
+    let mut characters = text.chars();
+
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        for column in 0..width() {
+            let character = ScreenChar {
+                ascii_character: characters.next().map_or(b' ', encoding::encode),
+                color_code
+            };
+
+            show_cell(height(), column, character, None);
+        }
     });
 }
 
+// Only changes what the writers assume, the hardware is set up by whoever calls this.
+// The writers have to be resized afterwards.
+pub fn set_size(width: usize, height: usize, character_height: u8) {
+    WIDTH.store(width.min(MAX_WIDTH), Ordering::Relaxed);
+    HEIGHT.store(height.min(MAX_HEIGHT), Ordering::Relaxed);
+    CHARACTER_HEIGHT.store(character_height, Ordering::Relaxed);
+}
+
+// Draws into its own screen, which is copied to VGA memory while it is the active one.
 pub struct Writer {
     pub column_position: usize,
     pub row_position: usize,
     color_code: ColorCode,
-    buffer: &'static mut Buffer
+    // What resets go back to, chosen with the color command.
+    default_color_code: ColorCode,
+    screen: [Line; MAX_HEIGHT],
+    active: bool,
+    scrollback: VecDeque<ScrollbackLine>,
+    // How far the view is scrolled back. The live screen is kept aside meanwhile.
+    view_offset: usize,
+    live_screen: Option<Box<[Line; MAX_HEIGHT]>>,
+    pointer: Option<Overlay>,
+    selection: Vec<Overlay>,
+    parser: Parser,
+    decoder: Utf8Decoder,
+    saved_position: (usize, usize),
+    bold: bool,
+    cursor_style: CursorStyle,
+    // The cell the cursor is drawn over on a framebuffer, which has no hardware cursor.
+    drawn_cursor: Option<(usize, usize)>,
+    cursor_visible: bool,
+    // Set while a full-screen application draws, which leaves the scrollback alone.
+    alternate: Option<SavedScreen>
 }
 
 impl Writer {
-    pub fn row_into_string(&self, row: usize) -> String {
-        let mut result: String = String::new();
+    pub fn new() -> Writer {
+        let blank = ScreenChar {
+            ascii_character: 0x0,
+            color_code: ColorCode::new(Color::White, Color::Black)
+        };
 
-        for col in 0..BUFFER_WIDTH {
-            let screen_char = self.buffer.chars[row][col].read();
+        Writer {
+            column_position: 0,
+            row_position: 0,
+            color_code: blank.color_code,
+            default_color_code: blank.color_code,
+            screen: [[blank; MAX_WIDTH]; MAX_HEIGHT],
+            active: false,
+            scrollback: VecDeque::new(),
+            view_offset: 0,
+            live_screen: None,
+            pointer: None,
+            selection: Vec::new(),
+            parser: Parser::new(),
+            decoder: Utf8Decoder::new(),
+            saved_position: (0, 0),
+            bold: false,
+            cursor_style: CursorStyle::Block,
+            drawn_cursor: None,
+            cursor_visible: true,
+            alternate: None
+        }
+    }
 
-            if screen_char.ascii_character == 0x0 {
-                break;
-            }
+    // Makes this the writer shown on screen.
+    pub fn activate(&mut self) {
+        self.active = true;
+        self.drawn_cursor = None;
 
-            result.push(char::from(screen_char.ascii_character));
+        for row in 0..height() {
+            for col in 0..width() {
+                self.show(row, col, self.screen[row][col]);
+            }
         }
 
-        result
+        self.set_cursor_style(self.cursor_style);
+        self.update_cursor();
     }
 
-    pub fn change_color_code(&mut self, color_code: ColorCode) {
-        self.color_code = color_code;
-    }
+    // Fits the text into a new screen size. Rows that no longer fit above the cursor go
+    // into the scrollback like when scrolling, columns that no longer fit are cut off.
+    pub fn resize(&mut self) {
+        self.reset_view();
+        self.hide_overlays();
 
-    pub fn clear(&mut self) {
-        let blank = ScreenChar {
-            ascii_character: 0x0,
-            color_code: self.color_code
-        };
+        let (width, height) = (width(), height());
+        let blank = self.blank();
+        let shift = (self.row_position + 1).saturating_sub(height);
+
+        for row in 0..shift {
+            let line = self.screen[row];
+            self.push_scrollback(&line);
+        }
 
-        for row in 0..BUFFER_HEIGHT {
-            for col in 0..BUFFER_WIDTH {
-                self.buffer.chars[row][col].write(blank);
+        for row in 0..MAX_HEIGHT {
+            for column in 0..MAX_WIDTH {
+                self.screen[row][column] = match self.screen.get(row + shift) {
+                    Some(line) if row < height && column < width => line[column],
+                    _ => blank
+                };
             }
         }
 
-        self.row_position = 0;
-        self.column_position = 0;
-    }
+        self.row_position -= shift;
+        self.column_position = self.column_position.min(width);
+        self.saved_position = (self.saved_position.0.min(height - 1), self.saved_position.1.min(width - 1));
 
-    pub fn move_left(&mut self) {
-        if 0 < self.column_position {
-            self.default_current();
-            self.column_position -= 1;
-            self.highlight_current();
+        if self.active {
+            self.activate();
         }
     }
 
-    pub fn move_right(&mut self) {
-        if self.buffer.chars[self.row_position][self.column_position - 1].read().ascii_character != 0x0 && self.column_position != 0 {
-            self.default_current();
-            self.column_position += 1;
-            self.highlight_current();
+    // Stops showing this writer. The pointer stays with the screen, not the writer.
+    pub fn deactivate(&mut self) {
+        self.hide_overlays();
+        self.active = false;
+    }
+
+    fn cell(&self, row: usize, column: usize) -> ScreenChar {
+        self.screen[row][column]
+    }
+
+    fn set_cell(&mut self, row: usize, column: usize, character: ScreenChar) {
+        self.screen[row][column] = character;
+
+        if self.active {
+            self.show(row, column, character);
         }
     }
 
-    pub fn backspace(&mut self) {
+    fn show(&self, row: usize, column: usize, character: ScreenChar) {
+        let cursor = if self.drawn_cursor == Some((row, column)) { Some(self.cursor_style) } else { None };
+
+        show_cell(row, column, character, cursor);
+    }
+
+    // Keeps the background, for highlighting a message.
+    pub fn change_foreground(&mut self, color: Color) {
+        self.color_code = self.color_code.with_foreground(color);
+    }
+
+    pub fn default_color_code(&self) -> ColorCode {
+        self.default_color_code
+    }
+
+    pub fn set_default_color_code(&mut self, color_code: ColorCode) {
+        self.default_color_code = color_code;
+        self.color_code = color_code;
+    }
+
+    pub fn reset_color_code(&mut self) {
+        self.color_code = self.default_color_code;
+    }
+
+    // The cleared text isn't lost, it moves into the scrollback.
+    pub fn clear(&mut self) {
+        self.reset_view();
+        let pointer = self.hide_overlays();
+
+        let used = if self.column_position == 0 { self.row_position } else { self.row_position + 1 };
+
+        for row in 0..used.min(height()) {
+            let line = self.read_line(row);
+            self.push_scrollback(&line);
+        }
+
         let blank = ScreenChar {
             ascii_character: 0x0,
             color_code: self.color_code
         };
 
-        if 0 < self.column_position {
-            self.default_current();
-
-            let row = self.row_position;
-            let col = self.column_position;
+        for row in 0..height() {
+            for col in 0..width() {
+                self.set_cell(row, col, blank);
+            }
+        }
 
-            self.buffer.chars[row][col - 1].write(blank);
-            self.column_position -= 1;
+        self.row_position = 0;
+        self.column_position = 0;
 
-            self.highlight_current();
-        }
+        self.update_cursor();
+        self.show_overlays(pointer);
     }
 
     pub fn write_byte(&mut self, byte: u8) {
-       self.default_current();
+        self.reset_view();
 
         match byte {
             b'\n' => self.new_line(),
             byte => {
-                if self.column_position >= BUFFER_WIDTH {
+                if self.column_position >= width() {
                     self.new_line();
                 }
 
@@ -175,7 +494,7 @@ impl Writer {
 
                 let color_code = self.color_code;
 
-                self.buffer.chars[row][col].write(ScreenChar {
+                self.set_cell(row, col, ScreenChar {
                     ascii_character: byte,
                     color_code
                 });
@@ -184,40 +503,152 @@ impl Writer {
             }
         }
 
-        self.highlight_current();
+        self.update_cursor();
     }
 
-    fn default_current(&mut self) {
-        let defaulted = ScreenChar {
-            ascii_character: self.buffer.chars[self.row_position][self.column_position].read().ascii_character,
-            color_code: ColorCode::new(Color::White, Color::Black)
+    // Puts a glyph with its own colours, without moving the cursor.
+    pub fn put(&mut self, row: usize, column: usize, byte: u8, color_code: ColorCode) {
+        if height() <= row || width() <= column {
+            return;
+        }
+
+        self.set_cell(row, column, ScreenChar {
+            ascii_character: byte,
+            color_code
+        });
+    }
+
+    pub fn write_at(&mut self, row: usize, column: usize, byte: u8) {
+        self.reset_view();
+
+        let color_code = self.color_code;
+
+        self.set_cell(row, column, ScreenChar {
+            ascii_character: byte,
+            color_code
+        });
+    }
+
+    pub fn move_to(&mut self, row: usize, column: usize) {
+        self.reset_view();
+        self.row_position = row;
+        self.column_position = column;
+        self.update_cursor();
+    }
+
+    // Moves the hardware cursor to the current position. While the view is scrolled
+    // back it is parked below the screen, which hides it.
+    fn update_cursor(&mut self) {
+        if !self.active {
+            return;
+        }
+
+        if framebuffer::text_console().is_some() {
+            if let Some((row, column)) = self.drawn_cursor.take() {
+                self.show(row, column, self.screen[row][column]);
+            }
+
+            if self.view_offset == 0 && self.cursor_visible {
+                let (row, column) = (self.row_position, self.column_position.min(width() - 1));
+
+                self.drawn_cursor = Some((row, column));
+                self.show(row, column, self.screen[row][column]);
+            }
+
+            return;
+        }
+
+        let position = if self.view_offset == 0 && self.cursor_visible {
+            self.row_position * width() + self.column_position.min(width() - 1)
+        } else {
+            width() * screen_height()
         };
 
-        self.buffer.chars[self.row_position][self.column_position].write(defaulted)
+        write_crtc(CURSOR_LOCATION_LOW, (position & 0xFF) as u8);
+        write_crtc(CURSOR_LOCATION_HIGH, (position >> 8) as u8);
     }
 
-    fn highlight_current(&mut self) {
-        let defaulted = ScreenChar {
-            ascii_character: self.buffer.chars[self.row_position][self.column_position].read().ascii_character,
-            color_code: ColorCode::new(Color::Black, Color::White)
+    pub fn set_cursor_style(&mut self, style: CursorStyle) {
+        self.cursor_style = style;
+
+        if !self.active {
+            return;
+        }
+
+        if framebuffer::text_console().is_some() {
+            self.update_cursor();
+            return;
+        }
+
+        let character_height = CHARACTER_HEIGHT.load(Ordering::Relaxed);
+
+        let (start, end) = match style {
+            CursorStyle::Block => (0, character_height - 1),
+            CursorStyle::Underline => (character_height - 2, character_height - 1)
         };
 
-        self.buffer.chars[self.row_position][self.column_position].write(defaulted)
+        // The upper bits of both registers belong to other settings.
+        write_crtc(CURSOR_START, (read_crtc(CURSOR_START) & 0xC0) | start);
+        write_crtc(CURSOR_END, (read_crtc(CURSOR_END) & 0xE0) | end);
     }
 
-    fn new_line(&mut self) {
-       if self.row_position + 1 == BUFFER_HEIGHT {
-            for row in 0..BUFFER_HEIGHT {
-                for col in 0..BUFFER_WIDTH {
-                    let character = self.buffer.chars[row][col].read();
-
-                    if 0 < row {
-                        self.buffer.chars[row - 1][col].write(character);
-                    }
-                }
-            }
+    pub fn cursor_style(&self) -> CursorStyle {
+        self.cursor_style
+    }
+
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        self.cursor_visible = visible;
+        self.update_cursor();
+    }
+
+    // Gives the screen to a full-screen application, starting out blank. The text on it
+    // is kept aside until leave_alternate_screen.
+    pub fn enter_alternate_screen(&mut self) {
+        if self.alternate.is_some() {
+            return;
+        }
+
+        self.reset_view();
+        self.hide_overlays();
+
+        let mut screen = self.blank_screen();
+
+        for row in 0..height() {
+            screen[row] = self.read_line(row);
+        }
+
+        self.alternate = Some(SavedScreen {
+            screen,
+            position: (self.row_position, self.column_position),
+            color_code: self.color_code
+        });
+
+        self.color_code = self.default_color_code;
+        self.erase(0, width() * height());
+        self.move_to(0, 0);
+    }
+
+    // Puts the text from before enter_alternate_screen back, with the cursor where it was.
+    pub fn leave_alternate_screen(&mut self) {
+        let saved = match self.alternate.take() {
+            Some(saved) => saved,
+            None => return
+        };
+
+        self.hide_overlays();
+
+        for row in 0..height() {
+            self.write_line(row, &saved.screen[row]);
+        }
 
-            self.clear_row(self.row_position);
+        self.color_code = saved.color_code;
+        self.cursor_visible = true;
+        self.move_to(saved.position.0.min(height() - 1), saved.position.1.min(width()));
+    }
+
+    fn new_line(&mut self) {
+       if self.row_position + 1 == height() {
+            self.scroll_up();
         } else {
             self.row_position += 1;
         }
@@ -225,25 +656,365 @@ impl Writer {
         self.column_position = 0;
     }
 
+    // Moves every row up by one and clears the last one. The position stays where it is.
+    // The top row goes into the scrollback.
+    pub fn scroll_up(&mut self) {
+        self.reset_view();
+        let pointer = self.hide_overlays();
+
+        let top = self.read_line(0);
+        self.push_scrollback(&top);
+
+        for row in 1..height() {
+            for col in 0..width() {
+                let character = self.cell(row, col);
+                self.set_cell(row - 1, col, character);
+            }
+        }
+
+        self.clear_row(height() - 1);
+        self.show_overlays(pointer);
+    }
+
     pub fn clear_row(&mut self, row: usize) {
+        self.reset_view();
+
         let blank = ScreenChar {
             ascii_character: 0x0,
             color_code: self.color_code
         };
 
-        for col in 0..BUFFER_WIDTH {
-            self.buffer.chars[row][col].write(blank);
+        for col in 0..width() {
+            self.set_cell(row, col, blank);
+        }
+    }
+
+    // Scrolls the view by lines, positive ones go back into the scrollback. Any output
+    // brings it back to the bottom.
+    pub fn scroll_view(&mut self, lines: isize) {
+        if self.alternate.is_some() {
+            return;
+        }
+
+        let offset = (self.view_offset as isize + lines).max(0).min(self.scrollback.len() as isize) as usize;
+
+        if offset == self.view_offset {
+            return;
+        }
+
+        let pointer = self.hide_overlays();
+
+        if self.view_offset == 0 {
+            let mut live = self.blank_screen();
+
+            for row in 0..height() {
+                live[row] = self.read_line(row);
+            }
+
+            self.live_screen = Some(live);
+        }
+
+        self.view_offset = offset;
+
+        for row in 0..height() {
+            let index = self.scrollback.len() - offset + row;
+
+            let line = match self.scrollback.get(index) {
+                Some(line) => line.to_line(),
+                None => self.live_screen.as_ref().expect("Live screen not saved.")[index - self.scrollback.len()]
+            };
+
+            self.write_line(row, &line);
+        }
+
+        if offset == 0 {
+            self.live_screen = None;
+        }
+
+        self.update_cursor();
+        self.show_overlays(pointer);
+    }
+
+    fn push_scrollback(&mut self, line: &Line) {
+        if self.alternate.is_some() {
+            return;
+        }
+
+        // Room for all lines up front, so the queue never grows past what the heap is
+        // sized for.
+        if self.scrollback.capacity() == 0 {
+            self.scrollback.reserve_exact(SCROLLBACK_LINES + 1);
+        }
+
+        self.scrollback.push_back(ScrollbackLine::new(line));
+
+        if SCROLLBACK_LINES < self.scrollback.len() {
+            self.scrollback.pop_front();
+        }
+    }
+
+    fn reset_view(&mut self) {
+        if self.view_offset != 0 {
+            self.scroll_view(-(self.view_offset as isize));
+        }
+    }
+
+    pub fn show_pointer(&mut self, row: usize, column: usize) {
+        self.hide_pointer();
+        self.pointer = Some(self.draw_overlay(row, column));
+    }
+
+    pub fn hide_pointer(&mut self) -> Option<(usize, usize)> {
+        let pointer = self.pointer.take()?;
+        let position = (pointer.row, pointer.column);
+
+        self.erase_overlay(&pointer);
+
+        Some(position)
+    }
+
+    // Selects every cell from start to end, both given as (row, column), in reading order.
+    pub fn select(&mut self, start: (usize, usize), end: (usize, usize)) {
+        let pointer = self.hide_overlays();
+
+        let (start, end) = if end < start { (end, start) } else { (start, end) };
+
+        for cell in (start.0 * width() + start.1)..=(end.0 * width() + end.1) {
+            let overlay = self.draw_overlay(cell / width(), cell % width());
+            self.selection.push(overlay);
+        }
+
+        self.show_overlays(pointer);
+    }
+
+    pub fn clear_selection(&mut self) {
+        let pointer = self.hide_overlays();
+        self.show_overlays(pointer);
+    }
+
+    // The selected text, one line per row without trailing blanks.
+    pub fn selected_text(&self) -> String {
+        let mut lines: Vec<String> = Vec::new();
+        let mut row = None;
+
+        for overlay in &self.selection {
+            if row != Some(overlay.row) {
+                row = Some(overlay.row);
+                lines.push(String::new());
+            }
+
+            let character = encoding::from_cp437(overlay.original.ascii_character);
+
+            lines.last_mut().expect("Line pushed above.").push(character);
+        }
+
+        let lines: Vec<&str> = lines.iter().map(|line| line.trim_end()).collect();
+        lines.join("\n")
+    }
+
+    // Takes the pointer and selection off the screen before its contents move, returning
+    // where the pointer was so it can be put back.
+    fn hide_overlays(&mut self) -> Option<(usize, usize)> {
+        let pointer = self.hide_pointer();
+
+        for overlay in core::mem::take(&mut self.selection).iter().rev() {
+            self.erase_overlay(overlay);
+        }
+
+        pointer
+    }
+
+    fn show_overlays(&mut self, pointer: Option<(usize, usize)>) {
+        if let Some((row, column)) = pointer {
+            self.show_pointer(row, column);
+        }
+    }
+
+    fn draw_overlay(&mut self, row: usize, column: usize) -> Overlay {
+        let original = self.cell(row, column);
+        let drawn = ScreenChar {
+            ascii_character: original.ascii_character,
+            color_code: original.color_code.inverted()
+        };
+
+        self.set_cell(row, column, drawn);
+
+        Overlay { row, column, original, drawn }
+    }
+
+    // Leaves the cell alone if something was written over the overlay in the meantime.
+    fn erase_overlay(&mut self, overlay: &Overlay) {
+        if self.cell(overlay.row, overlay.column) == overlay.drawn {
+            self.set_cell(overlay.row, overlay.column, overlay.original);
+        }
+    }
+
+    fn blank(&self) -> ScreenChar {
+        ScreenChar {
+            ascii_character: 0x0,
+            color_code: self.color_code
         }
     }
 
+    // Made on the heap right away, a whole screen is too big for the stack of a thread.
+    fn blank_screen(&self) -> Box<[Line; MAX_HEIGHT]> {
+        vec![[self.blank(); MAX_WIDTH]; MAX_HEIGHT].into_boxed_slice().try_into().expect("Screen has the wrong height.")
+    }
+
+    fn read_line(&self, row: usize) -> Line {
+        let mut line = [self.blank(); MAX_WIDTH];
+
+        for col in 0..width() {
+            line[col] = self.cell(row, col);
+        }
+
+        line
+    }
+
+    fn write_line(&mut self, row: usize, line: &Line) {
+        for col in 0..width() {
+            self.set_cell(row, col, line[col]);
+        }
+    }
+
+    // Output goes through the escape sequence parser, so colours, cursor movement and
+    // erasing work like on a terminal. Text is UTF-8 and shown with the font's code page.
     pub fn write_string(&mut self, s: &str) {
         for byte in s.bytes() {
-            match  byte {
-                0x20..=0x7E | b'\n' => self.write_byte(byte),
-                _ => self.write_byte(0xFE)
+            match self.parser.advance(byte) {
+                Some(Action::Print(byte)) => self.print_byte(byte),
+                Some(Action::Csi(csi)) => self.execute_csi(&csi),
+                Some(Action::SaveCursor) => self.saved_position = (self.row_position, self.column_position),
+                Some(Action::RestoreCursor) => self.move_to(self.saved_position.0, self.saved_position.1),
+                None => { }
             }
         }
     }
+
+    fn print_byte(&mut self, byte: u8) {
+        for character in self.decoder.advance(byte).iter().flatten() {
+            self.print_character(*character);
+        }
+    }
+
+    fn print_character(&mut self, character: char) {
+        match character {
+            '\n' => self.write_byte(b'\n'),
+            '\r' => self.move_to(self.row_position, 0),
+            '\u{8}' => {
+                if 0 < self.column_position {
+                    self.move_to(self.row_position, self.column_position - 1);
+                }
+            },
+            '\t' => {
+                let column = ((self.column_position / 8 + 1) * 8).min(width() - 1);
+                self.move_to(self.row_position, column);
+            },
+            character => self.write_byte(encoding::encode(character))
+        }
+    }
+
+    fn execute_csi(&mut self, csi: &Csi) {
+        let row = self.row_position as isize;
+        let column = self.column_position.min(width() - 1) as isize;
+        let count = csi.parameter(0, 1) as isize;
+
+        match csi.final_byte {
+            b'A' => self.move_clamped(row - count, column),
+            b'B' => self.move_clamped(row + count, column),
+            b'C' => self.move_clamped(row, column + count),
+            b'D' => self.move_clamped(row, column - count),
+            b'E' => self.move_clamped(row + count, 0),
+            b'F' => self.move_clamped(row - count, 0),
+            b'G' => self.move_clamped(row, count - 1),
+            b'd' => self.move_clamped(count - 1, column),
+            b'H' | b'f' => self.move_clamped(count - 1, csi.parameter(1, 1) as isize - 1),
+            b'J' => {
+                let cursor = row as usize * width() + column as usize;
+
+                match csi.raw_parameter(0) {
+                    0 => self.erase(cursor, width() * height()),
+                    1 => self.erase(0, cursor + 1),
+                    _ => self.erase(0, width() * height())
+                }
+            },
+            b'K' => {
+                let start = row as usize * width();
+                let cursor = start + column as usize;
+
+                match csi.raw_parameter(0) {
+                    0 => self.erase(cursor, start + width()),
+                    1 => self.erase(start, cursor + 1),
+                    _ => self.erase(start, start + width())
+                }
+            },
+            b'm' => {
+                for parameter in csi.parameters() {
+                    self.select_graphic_rendition(*parameter);
+                }
+            },
+            b's' => self.saved_position = (self.row_position, self.column_position),
+            b'u' => self.move_to(self.saved_position.0, self.saved_position.1),
+            _ => { }
+        }
+    }
+
+    fn move_clamped(&mut self, row: isize, column: isize) {
+        let row = row.max(0).min(height() as isize - 1) as usize;
+        let column = column.max(0).min(width() as isize - 1) as usize;
+
+        self.move_to(row, column);
+    }
+
+    // Blanks the cells from start up to end, counted from the top left corner.
+    fn erase(&mut self, start: usize, end: usize) {
+        self.reset_view();
+
+        let blank = self.blank();
+
+        for cell in start..end.min(width() * height()) {
+            self.set_cell(cell / width(), cell % width(), blank);
+        }
+
+        self.update_cursor();
+    }
+
+    // SGR: 0 resets, 1 and 22 switch bold (shown as the bright colours) on and off, 30-37
+    // and 90-97 set the foreground, 40-47 and 100-107 the background, 39 and 49 reset them.
+    fn select_graphic_rendition(&mut self, parameter: u16) {
+        // ANSI colour order mapped to VGA colours.
+        const COLORS: [u8; 8] = [0, 4, 2, 6, 1, 5, 3, 7];
+
+        let default = self.default_color_code.0;
+
+        let mut foreground = self.color_code.0 & 0xF;
+        let mut background = self.color_code.0 >> 4;
+
+        match parameter {
+            0 => {
+                self.bold = false;
+                foreground = default & 0xF;
+                background = default >> 4;
+            },
+            1 => {
+                self.bold = true;
+                foreground |= 0x8;
+            },
+            22 => {
+                self.bold = false;
+                foreground &= 0x7;
+            },
+            30..=37 => foreground = COLORS[(parameter - 30) as usize] | if self.bold { 0x8 } else { 0 },
+            39 => foreground = default & 0xF,
+            40..=47 => background = COLORS[(parameter - 40) as usize],
+            49 => background = default >> 4,
+            90..=97 => foreground = COLORS[(parameter - 90) as usize] | 0x8,
+            100..=107 => background = COLORS[(parameter - 100) as usize] | 0x8,
+            _ => { }
+        }
+
+        self.color_code = ColorCode(background << 4 | foreground);
+    }
 }
 
 impl fmt::Write for Writer {